@@ -0,0 +1,160 @@
+-- Tables and views the finder and the report tool work with.
+-- Starts from the schema of the original sandwich.sql dump and upgrades it, so that dbs created from the dump
+-- are brought up to date in place and new ones end up with the same schema.
+
+CREATE TABLE IF NOT EXISTS `block` (
+  `slot` bigint(20) NOT NULL,
+  `timestamp` bigint(20) NOT NULL,
+  `tx_count` int(11) NOT NULL,
+  PRIMARY KEY (`slot`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_general_ci;
+
+CREATE TABLE IF NOT EXISTS `transaction` (
+  `id` int(11) NOT NULL AUTO_INCREMENT,
+  `tx_hash` varchar(89) NOT NULL,
+  `signer` varchar(45) NOT NULL,
+  `slot` bigint(20) NOT NULL,
+  `order_in_block` int(11) NOT NULL,
+  PRIMARY KEY (`id`),
+  KEY `slot` (`slot`),
+  CONSTRAINT `transaction_ibfk_1` FOREIGN KEY (`slot`) REFERENCES `block` (`slot`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_general_ci;
+
+CREATE TABLE IF NOT EXISTS `sandwich` (
+  `id` int(11) NOT NULL AUTO_INCREMENT,
+  PRIMARY KEY (`id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_general_ci;
+
+CREATE TABLE IF NOT EXISTS `swap` (
+  `id` int(11) NOT NULL AUTO_INCREMENT,
+  `sandwich_id` int(11) NOT NULL,
+  `outer_program` varchar(45) DEFAULT NULL COMMENT 'wrapper program of the swap',
+  `inner_program` varchar(45) NOT NULL COMMENT 'facilitator program of the swap',
+  `amm` varchar(45) NOT NULL COMMENT 'market pubkey',
+  `subject` varchar(45) NOT NULL COMMENT 'beneficial owner of the tokens swapped',
+  `input_mint` varchar(45) NOT NULL,
+  `output_mint` varchar(45) NOT NULL,
+  `input_amount` varchar(45) NOT NULL,
+  `output_amount` varchar(45) NOT NULL,
+  `tx_id` int(11) NOT NULL,
+  `swap_type` enum('FRONTRUN','VICTIM','BACKRUN') NOT NULL,
+  PRIMARY KEY (`id`),
+  KEY `outer_program` (`outer_program`),
+  KEY `inner_program` (`inner_program`),
+  KEY `amm` (`amm`),
+  KEY `subject` (`subject`),
+  KEY `input_mint` (`input_mint`),
+  KEY `output_mint` (`output_mint`),
+  KEY `input_amount` (`input_amount`),
+  KEY `output_amount` (`output_amount`),
+  KEY `tx_id` (`tx_id`),
+  KEY `sandwich_id` (`sandwich_id`),
+  CONSTRAINT `swap_ibfk_1` FOREIGN KEY (`tx_id`) REFERENCES `transaction` (`id`),
+  CONSTRAINT `swap_ibfk_2` FOREIGN KEY (`sandwich_id`) REFERENCES `sandwich` (`id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_general_ci;
+
+-- the dump stored a transaction once per sandwich it was part of, keep the oldest row of each
+UPDATE `swap` `s`
+  JOIN `transaction` `t` ON `t`.`id` = `s`.`tx_id`
+  JOIN (SELECT `tx_hash`, MIN(`id`) AS `id` FROM `transaction` GROUP BY `tx_hash`) `k` ON `k`.`tx_hash` = `t`.`tx_hash`
+  SET `s`.`tx_id` = `k`.`id`
+  WHERE `s`.`tx_id` <> `k`.`id`;
+
+DELETE `t` FROM `transaction` `t`
+  JOIN (SELECT `tx_hash`, MIN(`id`) AS `id` FROM `transaction` GROUP BY `tx_hash`) `k` ON `k`.`tx_hash` = `t`.`tx_hash`
+  WHERE `t`.`id` <> `k`.`id`;
+
+ALTER TABLE `transaction`
+  ADD UNIQUE KEY `tx_hash` (`tx_hash`);
+
+ALTER TABLE `swap`
+  ADD COLUMN `input_decimals` tinyint(3) UNSIGNED DEFAULT NULL AFTER `output_amount`,
+  ADD COLUMN `output_decimals` tinyint(3) UNSIGNED DEFAULT NULL AFTER `input_decimals`,
+  ADD COLUMN `input_ui_amount` double DEFAULT NULL AFTER `output_decimals`,
+  ADD COLUMN `output_ui_amount` double DEFAULT NULL AFTER `input_ui_amount`;
+
+ALTER TABLE `sandwich`
+  ADD COLUMN `slot` bigint(20) NOT NULL COMMENT 'slot of the frontrun',
+  ADD COLUMN `frontrun_tx` varchar(89) NOT NULL,
+  ADD COLUMN `backrun_tx` varchar(89) NOT NULL,
+  ADD COLUMN `quote_mint` varchar(45) NOT NULL COMMENT 'mint paid in the frontrun and received in the backrun',
+  ADD COLUMN `profit` bigint(20) NOT NULL COMMENT 'backrun output - frontrun input, in quote_mint',
+  ADD COLUMN `leftover` bigint(20) NOT NULL COMMENT 'frontrun output - backrun input',
+  ADD COLUMN `fees` bigint(20) UNSIGNED NOT NULL COMMENT 'lamports, incl. priority fees',
+  ADD COLUMN `tips` bigint(20) UNSIGNED NOT NULL COMMENT 'lamports, jito tips',
+  ADD COLUMN `net_profit` bigint(20) DEFAULT NULL COMMENT 'profit - fees - tips, only when quote_mint is wsol',
+  ADD COLUMN `loss_sol` double DEFAULT NULL COMMENT 'total victim loss in sol',
+  ADD COLUMN `profit_sol` double DEFAULT NULL COMMENT 'net profit in sol',
+  ADD COLUMN `rule_set` varchar(45) NOT NULL DEFAULT 'default' COMMENT 'detection rule set that matched',
+  ADD COLUMN `rule_version` int(10) UNSIGNED NOT NULL DEFAULT 1,
+  ADD COLUMN `cross_slot` tinyint(1) NOT NULL DEFAULT 0 COMMENT 'legs landed in different slots of the leader group',
+  ADD COLUMN `confidence` double NOT NULL DEFAULT 1 COMMENT 'how likely this is an actual sandwich, 0-1',
+  ADD COLUMN `sandwicher_id` int(11) DEFAULT NULL COMMENT 'entity of the sandwicher, rewritten to the oldest id when entities merge, see sandwicher_member',
+  ADD COLUMN `frontrun_tip` bigint(20) UNSIGNED NOT NULL DEFAULT 0 COMMENT 'lamports, incl. a separate tip tx before the frontrun',
+  ADD COLUMN `backrun_tip` bigint(20) UNSIGNED NOT NULL DEFAULT 0 COMMENT 'lamports, incl. a separate tip tx after the backrun',
+  ADD COLUMN `bundle_id` varchar(89) DEFAULT NULL,
+  ADD COLUMN `bundled` tinyint(1) DEFAULT NULL COMMENT 'both legs in the same bundle, null without a bundle source',
+  ADD KEY `sandwicher_id` (`sandwicher_id`);
+
+-- sandwiches of the dump only had swaps, derive them from the legs. fees and tips weren't stored, so they stay 0
+-- and net_profit unknown. sandwiches whose profit doesn't fit are left empty and dropped below, as the finder does.
+UPDATE `sandwich` `s`
+  JOIN `swap` `f` ON `f`.`sandwich_id` = `s`.`id` AND `f`.`swap_type` = 'FRONTRUN'
+  JOIN `transaction` `ft` ON `ft`.`id` = `f`.`tx_id`
+  JOIN `swap` `b` ON `b`.`sandwich_id` = `s`.`id` AND `b`.`swap_type` = 'BACKRUN'
+  JOIN `transaction` `bt` ON `bt`.`id` = `b`.`tx_id`
+  SET `s`.`slot` = `ft`.`slot`,
+    `s`.`frontrun_tx` = `ft`.`tx_hash`,
+    `s`.`backrun_tx` = `bt`.`tx_hash`,
+    `s`.`quote_mint` = `f`.`input_mint`,
+    `s`.`profit` = CAST(`b`.`output_amount` AS DECIMAL(20,0)) - CAST(`f`.`input_amount` AS DECIMAL(20,0)),
+    `s`.`leftover` = CAST(`f`.`output_amount` AS DECIMAL(20,0)) - CAST(`b`.`input_amount` AS DECIMAL(20,0)),
+    `s`.`cross_slot` = `ft`.`slot` <> `bt`.`slot`
+  WHERE `s`.`frontrun_tx` = ''
+    AND CAST(`b`.`output_amount` AS DECIMAL(20,0)) - CAST(`f`.`input_amount` AS DECIMAL(20,0)) BETWEEN -9223372036854775808 AND 9223372036854775807
+    AND CAST(`f`.`output_amount` AS DECIMAL(20,0)) - CAST(`b`.`input_amount` AS DECIMAL(20,0)) BETWEEN -9223372036854775808 AND 9223372036854775807;
+
+-- the dump could store a sandwich twice, keep the oldest one and drop those without both legs
+DELETE `sw` FROM `swap` `sw`
+  JOIN `sandwich` `s` ON `s`.`id` = `sw`.`sandwich_id`
+  JOIN (SELECT `slot`, `frontrun_tx`, `backrun_tx`, MIN(`id`) AS `id` FROM `sandwich` GROUP BY `slot`, `frontrun_tx`, `backrun_tx`) `k`
+    ON `k`.`slot` = `s`.`slot` AND `k`.`frontrun_tx` = `s`.`frontrun_tx` AND `k`.`backrun_tx` = `s`.`backrun_tx`
+  WHERE `s`.`id` <> `k`.`id` OR `s`.`frontrun_tx` = '';
+
+DELETE `s` FROM `sandwich` `s`
+  JOIN (SELECT `slot`, `frontrun_tx`, `backrun_tx`, MIN(`id`) AS `id` FROM `sandwich` GROUP BY `slot`, `frontrun_tx`, `backrun_tx`) `k`
+    ON `k`.`slot` = `s`.`slot` AND `k`.`frontrun_tx` = `s`.`frontrun_tx` AND `k`.`backrun_tx` = `s`.`backrun_tx`
+  WHERE `s`.`id` <> `k`.`id` OR `s`.`frontrun_tx` = '';
+
+ALTER TABLE `sandwich`
+  ADD UNIQUE KEY `natural_key` (`slot`,`frontrun_tx`,`backrun_tx`,`rule_set`);
+
+CREATE TABLE IF NOT EXISTS `slot_status` (
+  `slot` bigint(20) NOT NULL,
+  `status` enum('OBSERVED','SKIPPED','MISSED') NOT NULL COMMENT 'slots without a row were never observed',
+  PRIMARY KEY (`slot`),
+  KEY `status` (`status`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_general_ci;
+
+CREATE TABLE IF NOT EXISTS `leader_schedule` (
+  `slot` bigint(20) NOT NULL,
+  `leader` varchar(45) NOT NULL COMMENT 'identity pubkey of the leader',
+  PRIMARY KEY (`slot`),
+  KEY `leader` (`leader`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_general_ci;
+
+CREATE TABLE IF NOT EXISTS `sandwicher_member` (
+  `member` varchar(45) NOT NULL COMMENT 'wallet or program linked to the sandwicher',
+  `sandwicher_id` int(11) NOT NULL COMMENT 'a member with several ids links them together',
+  PRIMARY KEY (`member`,`sandwicher_id`),
+  KEY `sandwicher_id` (`sandwicher_id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_general_ci;
+
+-- blocks stored before slot_status existed were observed
+INSERT IGNORE INTO `slot_status` (`slot`, `status`) SELECT `slot`, 'OBSERVED' FROM `block`;
+
+CREATE OR REPLACE VIEW `sandwich_view` AS SELECT `t`.`tx_hash` AS `tx_hash`, `t`.`signer` AS `signer`, `t`.`slot` AS `slot`, `t`.`order_in_block` AS `order_in_block`, `s`.`sandwich_id` AS `sandwich_id`, `s`.`outer_program` AS `outer_program`, `s`.`inner_program` AS `inner_program`, `s`.`amm` AS `amm`, `s`.`subject` AS `subject`, `s`.`input_amount` AS `input_amount`, `s`.`input_mint` AS `input_mint`, `s`.`output_amount` AS `output_amount`, `s`.`output_mint` AS `output_mint`, `s`.`swap_type` AS `swap_type` FROM ((`swap` `s` join `transaction` `t`) join `block` `b`) WHERE `s`.`tx_id` = `t`.`id` AND `t`.`slot` = `b`.`slot` ORDER BY `s`.`sandwich_id` ASC, `s`.`tx_id` ASC;
+
+CREATE OR REPLACE VIEW `sandwicher_stats` AS SELECT `sandwich`.`sandwicher_id` AS `sandwicher_id`, `sandwich`.`rule_set` AS `rule_set`, count(0) AS `sandwiches`, sum(`sandwich`.`profit_sol`) AS `profit_sol`, sum(`sandwich`.`loss_sol`) AS `loss_sol` FROM `sandwich` WHERE `sandwich`.`sandwicher_id` is not null GROUP BY `sandwich`.`sandwicher_id`, `sandwich`.`rule_set`;
+
+CREATE OR REPLACE VIEW `swaps_by_wrapper` AS SELECT `sandwich_view`.`outer_program` AS `outer_program`, `sandwich_view`.`swap_type` AS `swap_type`, count(0) AS `count(*)` FROM `sandwich_view` GROUP BY `sandwich_view`.`outer_program`, `sandwich_view`.`swap_type` ORDER BY `sandwich_view`.`swap_type` ASC, count(0) ASC;
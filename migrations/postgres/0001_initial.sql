@@ -0,0 +1,98 @@
+-- Tables and views the finder and the report tool work with, as in the mysql schema.
+-- There are no unsigned types, and enums are checked varchars.
+-- Tables are created without IF NOT EXISTS, so a db that already has some fails the migration instead of being stamped.
+
+CREATE TABLE block (
+  slot bigint NOT NULL PRIMARY KEY,
+  "timestamp" bigint NOT NULL,
+  tx_count integer NOT NULL
+);
+
+CREATE TABLE "transaction" (
+  id bigserial PRIMARY KEY,
+  tx_hash varchar(89) NOT NULL UNIQUE,
+  signer varchar(45) NOT NULL,
+  slot bigint NOT NULL REFERENCES block (slot),
+  order_in_block integer NOT NULL
+);
+CREATE INDEX transaction_slot ON "transaction" (slot);
+
+CREATE TABLE sandwich (
+  id bigserial PRIMARY KEY,
+  slot bigint NOT NULL,
+  frontrun_tx varchar(89) NOT NULL,
+  backrun_tx varchar(89) NOT NULL,
+  quote_mint varchar(45) NOT NULL,
+  profit bigint NOT NULL,
+  leftover bigint NOT NULL,
+  fees bigint NOT NULL,
+  tips bigint NOT NULL,
+  net_profit bigint DEFAULT NULL,
+  loss_sol double precision DEFAULT NULL,
+  profit_sol double precision DEFAULT NULL,
+  rule_set varchar(45) NOT NULL DEFAULT 'default',
+  rule_version integer NOT NULL DEFAULT 1,
+  cross_slot boolean NOT NULL DEFAULT false,
+  confidence double precision NOT NULL DEFAULT 1,
+  sandwicher_id bigint DEFAULT NULL,
+  frontrun_tip bigint NOT NULL DEFAULT 0,
+  backrun_tip bigint NOT NULL DEFAULT 0,
+  bundle_id varchar(89) DEFAULT NULL,
+  bundled boolean DEFAULT NULL,
+  CONSTRAINT sandwich_natural_key UNIQUE (slot, frontrun_tx, backrun_tx, rule_set)
+);
+CREATE INDEX sandwich_sandwicher_id ON sandwich (sandwicher_id);
+
+CREATE TABLE swap (
+  id bigserial PRIMARY KEY,
+  sandwich_id bigint NOT NULL REFERENCES sandwich (id),
+  outer_program varchar(45) DEFAULT NULL,
+  inner_program varchar(45) NOT NULL,
+  amm varchar(45) NOT NULL,
+  subject varchar(45) NOT NULL,
+  input_mint varchar(45) NOT NULL,
+  output_mint varchar(45) NOT NULL,
+  input_amount varchar(45) NOT NULL,
+  output_amount varchar(45) NOT NULL,
+  input_decimals smallint DEFAULT NULL,
+  output_decimals smallint DEFAULT NULL,
+  input_ui_amount double precision DEFAULT NULL,
+  output_ui_amount double precision DEFAULT NULL,
+  tx_id bigint NOT NULL REFERENCES "transaction" (id),
+  swap_type varchar(8) NOT NULL CHECK (swap_type IN ('FRONTRUN', 'VICTIM', 'BACKRUN'))
+);
+CREATE INDEX swap_outer_program ON swap (outer_program);
+CREATE INDEX swap_inner_program ON swap (inner_program);
+CREATE INDEX swap_amm ON swap (amm);
+CREATE INDEX swap_subject ON swap (subject);
+CREATE INDEX swap_input_mint ON swap (input_mint);
+CREATE INDEX swap_output_mint ON swap (output_mint);
+CREATE INDEX swap_input_amount ON swap (input_amount);
+CREATE INDEX swap_output_amount ON swap (output_amount);
+CREATE INDEX swap_tx_id ON swap (tx_id);
+CREATE INDEX swap_sandwich_id ON swap (sandwich_id);
+
+CREATE TABLE slot_status (
+  slot bigint NOT NULL PRIMARY KEY,
+  status varchar(8) NOT NULL CHECK (status IN ('OBSERVED', 'SKIPPED', 'MISSED'))
+);
+CREATE INDEX slot_status_status ON slot_status (status);
+
+CREATE TABLE leader_schedule (
+  slot bigint NOT NULL PRIMARY KEY,
+  leader varchar(45) NOT NULL
+);
+CREATE INDEX leader_schedule_leader ON leader_schedule (leader);
+
+CREATE TABLE sandwicher_member (
+  member varchar(45) NOT NULL,
+  sandwicher_id bigint NOT NULL,
+  PRIMARY KEY (member, sandwicher_id)
+);
+CREATE INDEX sandwicher_member_sandwicher_id ON sandwicher_member (sandwicher_id);
+
+CREATE VIEW sandwich_view AS SELECT t.tx_hash, t.signer, t.slot, t.order_in_block, s.sandwich_id, s.outer_program, s.inner_program, s.amm, s.subject, s.input_amount, s.input_mint, s.output_amount, s.output_mint, s.swap_type FROM swap s JOIN "transaction" t ON s.tx_id = t.id JOIN block b ON t.slot = b.slot ORDER BY s.sandwich_id ASC, s.tx_id ASC;
+
+CREATE VIEW sandwicher_stats AS SELECT sandwicher_id, rule_set, count(*) AS sandwiches, sum(profit_sol) AS profit_sol, sum(loss_sol) AS loss_sol FROM sandwich WHERE sandwicher_id IS NOT NULL GROUP BY sandwicher_id, rule_set;
+
+CREATE VIEW swaps_by_wrapper AS SELECT outer_program, swap_type, count(*) AS "count(*)" FROM sandwich_view GROUP BY outer_program, swap_type ORDER BY swap_type ASC, count(*) ASC;
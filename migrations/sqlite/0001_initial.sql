@@ -0,0 +1,98 @@
+-- Tables and views the finder and the report tool work with, as in the mysql schema.
+-- Enums are checked text columns.
+-- Tables are created without IF NOT EXISTS, so a db that already has some fails the migration instead of being stamped.
+
+CREATE TABLE block (
+  slot INTEGER NOT NULL PRIMARY KEY,
+  timestamp INTEGER NOT NULL,
+  tx_count INTEGER NOT NULL
+);
+
+CREATE TABLE "transaction" (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  tx_hash TEXT NOT NULL UNIQUE,
+  signer TEXT NOT NULL,
+  slot INTEGER NOT NULL REFERENCES block (slot),
+  order_in_block INTEGER NOT NULL
+);
+CREATE INDEX transaction_slot ON "transaction" (slot);
+
+CREATE TABLE sandwich (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  slot INTEGER NOT NULL,
+  frontrun_tx TEXT NOT NULL,
+  backrun_tx TEXT NOT NULL,
+  quote_mint TEXT NOT NULL,
+  profit INTEGER NOT NULL,
+  leftover INTEGER NOT NULL,
+  fees INTEGER NOT NULL,
+  tips INTEGER NOT NULL,
+  net_profit INTEGER DEFAULT NULL,
+  loss_sol REAL DEFAULT NULL,
+  profit_sol REAL DEFAULT NULL,
+  rule_set TEXT NOT NULL DEFAULT 'default',
+  rule_version INTEGER NOT NULL DEFAULT 1,
+  cross_slot INTEGER NOT NULL DEFAULT 0,
+  confidence REAL NOT NULL DEFAULT 1,
+  sandwicher_id INTEGER DEFAULT NULL,
+  frontrun_tip INTEGER NOT NULL DEFAULT 0,
+  backrun_tip INTEGER NOT NULL DEFAULT 0,
+  bundle_id TEXT DEFAULT NULL,
+  bundled INTEGER DEFAULT NULL,
+  UNIQUE (slot, frontrun_tx, backrun_tx, rule_set)
+);
+CREATE INDEX sandwich_sandwicher_id ON sandwich (sandwicher_id);
+
+CREATE TABLE swap (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  sandwich_id INTEGER NOT NULL REFERENCES sandwich (id),
+  outer_program TEXT DEFAULT NULL,
+  inner_program TEXT NOT NULL,
+  amm TEXT NOT NULL,
+  subject TEXT NOT NULL,
+  input_mint TEXT NOT NULL,
+  output_mint TEXT NOT NULL,
+  input_amount TEXT NOT NULL,
+  output_amount TEXT NOT NULL,
+  input_decimals INTEGER DEFAULT NULL,
+  output_decimals INTEGER DEFAULT NULL,
+  input_ui_amount REAL DEFAULT NULL,
+  output_ui_amount REAL DEFAULT NULL,
+  tx_id INTEGER NOT NULL REFERENCES "transaction" (id),
+  swap_type TEXT NOT NULL CHECK (swap_type IN ('FRONTRUN', 'VICTIM', 'BACKRUN'))
+);
+CREATE INDEX swap_outer_program ON swap (outer_program);
+CREATE INDEX swap_inner_program ON swap (inner_program);
+CREATE INDEX swap_amm ON swap (amm);
+CREATE INDEX swap_subject ON swap (subject);
+CREATE INDEX swap_input_mint ON swap (input_mint);
+CREATE INDEX swap_output_mint ON swap (output_mint);
+CREATE INDEX swap_input_amount ON swap (input_amount);
+CREATE INDEX swap_output_amount ON swap (output_amount);
+CREATE INDEX swap_tx_id ON swap (tx_id);
+CREATE INDEX swap_sandwich_id ON swap (sandwich_id);
+
+CREATE TABLE slot_status (
+  slot INTEGER NOT NULL PRIMARY KEY,
+  status TEXT NOT NULL CHECK (status IN ('OBSERVED', 'SKIPPED', 'MISSED'))
+);
+CREATE INDEX slot_status_status ON slot_status (status);
+
+CREATE TABLE leader_schedule (
+  slot INTEGER NOT NULL PRIMARY KEY,
+  leader TEXT NOT NULL
+);
+CREATE INDEX leader_schedule_leader ON leader_schedule (leader);
+
+CREATE TABLE sandwicher_member (
+  member TEXT NOT NULL,
+  sandwicher_id INTEGER NOT NULL,
+  PRIMARY KEY (member, sandwicher_id)
+);
+CREATE INDEX sandwicher_member_sandwicher_id ON sandwicher_member (sandwicher_id);
+
+CREATE VIEW sandwich_view AS SELECT t.tx_hash, t.signer, t.slot, t.order_in_block, s.sandwich_id, s.outer_program, s.inner_program, s.amm, s.subject, s.input_amount, s.input_mint, s.output_amount, s.output_mint, s.swap_type FROM swap s JOIN "transaction" t ON s.tx_id = t.id JOIN block b ON t.slot = b.slot ORDER BY s.sandwich_id ASC, s.tx_id ASC;
+
+CREATE VIEW sandwicher_stats AS SELECT sandwicher_id, rule_set, count(*) AS sandwiches, sum(profit_sol) AS profit_sol, sum(loss_sol) AS loss_sol FROM sandwich WHERE sandwicher_id IS NOT NULL GROUP BY sandwicher_id, rule_set;
+
+CREATE VIEW swaps_by_wrapper AS SELECT outer_program, swap_type, count(*) AS "count(*)" FROM sandwich_view GROUP BY outer_program, swap_type ORDER BY swap_type ASC, count(*) ASC;
@@ -0,0 +1,212 @@
+use std::str::FromStr;
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::decode::{Swap, DLMM_PUBKEY, METEORA_PUBKEY, PDF2_PUBKEY, PDF_PUBKEY, RAYDIUM_LP_PUBKEY, RAYDIUM_V4_PUBKEY, RAYDIUM_V5_PUBKEY, WHIRLPOOL_PUBKEY, WSOL_PUBKEY};
+
+/// How much worse off a victim is compared to the same swap without the frontrun
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VictimLoss {
+    /// extra input paid for the output actually received
+    pub input: u64,
+    /// output missed for the input actually paid
+    pub output: u64,
+}
+
+/// A pricing model of an amm used to reconstruct its state from the swaps of a sandwich
+pub trait AmmModel: Send + Sync {
+    /// Estimates the loss of each victim, in order, given they all trade in the frontrun's direction
+    fn victim_losses(&self, frontrun: &Swap, victims: &[Swap]) -> Result<Vec<VictimLoss>, String>;
+}
+
+/// x * y = k with the fee taken from the input, e.g. raydium v4/cpmm, pump amm
+pub struct ConstantProduct {
+    pub fee: f64,
+}
+
+/// Constant product over virtual reserves with the fee always taken in sol, e.g. pump.fun, raydium launchpad
+pub struct BondingCurve {
+    pub fee: f64,
+}
+
+/// Concentrated liquidity approximated as constant product over the virtual reserves of the active range, e.g. whirlpool, dlmm.
+/// Doesn't model crossing into ranges of different liquidity, swaps that do are likely to be rejected as inconsistent.
+pub struct Clmm {
+    pub fee: f64,
+}
+
+/// Fee fractions applied to the input and output amounts of a swap
+struct Fees {
+    input: f64,
+    output: f64,
+}
+
+impl Fees {
+    /// Amount that actually enters the curve
+    fn net_input(&self, amount: f64) -> f64 {
+        amount * (1.0 - self.input)
+    }
+
+    /// Amount that actually left the curve
+    fn gross_output(&self, amount: f64) -> f64 {
+        amount / (1.0 - self.output)
+    }
+}
+
+/// Solves for the reserves (x, y) before the frontrun from two consecutive swaps in the same direction
+fn fit_reserves(a1: f64, b1: f64, a2: f64, b2: f64) -> Result<Option<(f64, f64)>, String> {
+    let (a3, b3) = (a1 + a2, b1 + b2);
+    let (c1, c2) = (-a1 * b1, -a3 * b3);
+    // | b1   -a1 | | x | = | c1 |
+    // | b3   -a3 | | y |   | c2 |
+    let det = a1 * b3 - b1 * a3;
+    // both swaps got the same price, i.e. no price impact observed
+    if det.abs() <= f64::EPSILON * (a1 * b3).abs() {
+        return Ok(None);
+    }
+    let x = (a1 * c2 - c1 * a3) / det;
+    let y = (b1 * c2 - b3 * c1) / det;
+    if !(x > 0.0 && y > b3) {
+        return Err(format!("inconsistent reserves ({x}, {y})"));
+    }
+    Ok(Some((x, y)))
+}
+
+/// Replays the victims against the fitted reserves without the frontrun
+fn fitted_losses(fees: &Fees, frontrun: &Swap, victims: &[Swap], flat_price_ok: bool) -> Result<Vec<VictimLoss>, String> {
+    let first = victims.first().ok_or("no victims")?;
+    if frontrun.input_amount == 0 || frontrun.output_amount == 0 || first.input_amount == 0 || first.output_amount == 0 {
+        return Err("zero amount swap".to_string());
+    }
+    let (a1, b1) = (fees.net_input(frontrun.input_amount as f64), fees.gross_output(frontrun.output_amount as f64));
+    let (a2, b2) = (fees.net_input(first.input_amount as f64), fees.gross_output(first.output_amount as f64));
+    let (mut x, mut y) = match fit_reserves(a1, b1, a2, b2)? {
+        Some(reserves) => reserves,
+        // the victims traded within a flat price range, so the frontrun didn't affect them
+        None if flat_price_ok => return Ok(victims.iter().map(|_| VictimLoss { input: 0, output: 0 }).collect()),
+        None => return Err("no price impact observed".to_string()),
+    };
+    Ok(victims.iter().map(|victim| {
+        let (input, output) = (victim.input_amount as f64, victim.output_amount as f64);
+        let net_input = fees.net_input(input);
+        let expected_gross = y * net_input / (x + net_input);
+        let expected_output = expected_gross * (1.0 - fees.output);
+        let gross_output = fees.gross_output(output);
+        let needed_input = if gross_output < y {
+            x * gross_output / (y - gross_output) / (1.0 - fees.input)
+        } else {
+            input
+        };
+        x += net_input;
+        y -= expected_gross;
+        VictimLoss {
+            input: (input - needed_input).max(0.0) as u64,
+            output: (expected_output - output).max(0.0) as u64,
+        }
+    }).collect())
+}
+
+impl AmmModel for ConstantProduct {
+    fn victim_losses(&self, frontrun: &Swap, victims: &[Swap]) -> Result<Vec<VictimLoss>, String> {
+        fitted_losses(&Fees { input: self.fee, output: 0.0 }, frontrun, victims, false)
+    }
+}
+
+impl AmmModel for BondingCurve {
+    fn victim_losses(&self, frontrun: &Swap, victims: &[Swap]) -> Result<Vec<VictimLoss>, String> {
+        // buys pay the fee on the way in, sells on the way out
+        let fees = if frontrun.input_mint == WSOL_PUBKEY.to_string() {
+            Fees { input: self.fee, output: 0.0 }
+        } else {
+            Fees { input: 0.0, output: self.fee }
+        };
+        fitted_losses(&fees, frontrun, victims, false)
+    }
+}
+
+impl AmmModel for Clmm {
+    fn victim_losses(&self, frontrun: &Swap, victims: &[Swap]) -> Result<Vec<VictimLoss>, String> {
+        fitted_losses(&Fees { input: self.fee, output: 0.0 }, frontrun, victims, true)
+    }
+}
+
+/// The model for the given swap program, using the typical fee tier of each dex
+pub fn model_for(program: &str) -> Box<dyn AmmModel> {
+    match Pubkey::from_str(program) {
+        Ok(RAYDIUM_V4_PUBKEY | RAYDIUM_V5_PUBKEY | PDF2_PUBKEY | METEORA_PUBKEY) => Box::new(ConstantProduct { fee: 0.0025 }),
+        Ok(PDF_PUBKEY) => Box::new(BondingCurve { fee: 0.01 }),
+        Ok(RAYDIUM_LP_PUBKEY) => Box::new(BondingCurve { fee: 0.0025 }),
+        Ok(WHIRLPOOL_PUBKEY) => Box::new(Clmm { fee: 0.003 }),
+        Ok(DLMM_PUBKEY) => Box::new(Clmm { fee: 0.0025 }),
+        _ => Box::new(ConstantProduct { fee: 0.0 }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::decode::fixtures::MINT_A as MINT;
+
+    /// A swap of `input_mint` for the other one of wsol and `MINT`
+    fn swap(input_mint: Pubkey, input_amount: u64, output_amount: u64) -> Swap {
+        let output_mint = if input_mint == WSOL_PUBKEY { MINT } else { WSOL_PUBKEY };
+        crate::decode::fixtures::swap(input_mint, output_mint, input_amount, output_amount)
+    }
+
+    // reserves of (1_000_000, 1_000_000): the frontrun swaps 250_000 for 200_000, moving them to (1_250_000, 800_000),
+    // after which the victim swaps 750_000 for 300_000. On its own the victim would have received
+    // 1_000_000 * 750_000 / 1_750_000 = 428_571.4, or only needed 1_000_000 * 300_000 / 700_000 = 428_571.4 for 300_000
+
+    #[test]
+    fn fits_reserves() {
+        assert_eq!(fit_reserves(250_000.0, 200_000.0, 750_000.0, 300_000.0), Ok(Some((1_000_000.0, 1_000_000.0))));
+        // same price for both swaps
+        assert_eq!(fit_reserves(100.0, 100.0, 200.0, 200.0), Ok(None));
+        // the victim got a better price after the frontrun
+        assert!(fit_reserves(100.0, 100.0, 100.0, 200.0).is_err());
+    }
+
+    #[test]
+    fn replays_victims_against_fitted_reserves() {
+        let fees = Fees { input: 0.0, output: 0.0 };
+        // the second victim continues from (2_000_000, 500_000) and swaps 500_000 for 100_000,
+        // without the frontrun it'd be at (1_750_000, 571_428.6) and receive 126_984.1, or need 371_212.1 for 100_000
+        let losses = fitted_losses(&fees, &swap(MINT, 250_000, 200_000), &[swap(MINT, 750_000, 300_000), swap(MINT, 500_000, 100_000)], false).unwrap();
+        assert_eq!(losses, vec![VictimLoss { input: 321_428, output: 128_571 }, VictimLoss { input: 128_787, output: 26_984 }]);
+        assert!(fitted_losses(&fees, &swap(MINT, 250_000, 200_000), &[], false).is_err());
+        assert!(fitted_losses(&fees, &swap(MINT, 250_000, 0), &[swap(MINT, 750_000, 300_000)], false).is_err());
+    }
+
+    #[test]
+    fn constant_product_takes_fee_from_input() {
+        // 20% of 312_500 and 937_500 leaves the same 250_000 and 750_000 on the curve, the victim would have needed 428_571.4 / 0.8
+        let model = ConstantProduct { fee: 0.2 };
+        assert_eq!(model.victim_losses(&swap(MINT, 312_500, 200_000), &[swap(MINT, 937_500, 300_000)]), Ok(vec![VictimLoss { input: 401_785, output: 128_571 }]));
+        assert!(model.victim_losses(&swap(MINT, 100, 100), &[swap(MINT, 200, 200)]).is_err());
+    }
+
+    #[test]
+    fn bonding_curve_takes_fee_in_sol() {
+        let model = BondingCurve { fee: 0.2 };
+        // buys pay the fee on the input like constant product
+        assert_eq!(model.victim_losses(&swap(WSOL_PUBKEY, 312_500, 200_000), &[swap(WSOL_PUBKEY, 937_500, 300_000)]), Ok(vec![VictimLoss { input: 401_785, output: 128_571 }]));
+        // sells receive 80% of what left the curve, 160_000 and 240_000 of 200_000 and 300_000,
+        // so the victim would have received 428_571.4 * 0.8
+        assert_eq!(model.victim_losses(&swap(MINT, 250_000, 160_000), &[swap(MINT, 750_000, 240_000)]), Ok(vec![VictimLoss { input: 321_428, output: 102_857 }]));
+    }
+
+    #[test]
+    fn clmm_accepts_flat_price() {
+        let model = Clmm { fee: 0.0 };
+        assert_eq!(model.victim_losses(&swap(MINT, 250_000, 200_000), &[swap(MINT, 750_000, 300_000)]), Ok(vec![VictimLoss { input: 321_428, output: 128_571 }]));
+        assert_eq!(model.victim_losses(&swap(MINT, 100, 100), &[swap(MINT, 200, 200)]), Ok(vec![VictimLoss { input: 0, output: 0 }]));
+    }
+
+    #[test]
+    fn picks_model_by_program() {
+        assert!(model_for(&RAYDIUM_LP_PUBKEY.to_string()).victim_losses(&swap(MINT, 250_000, 160_000), &[swap(MINT, 750_000, 240_000)]).is_ok());
+        // unknown programs are assumed to be fee-less constant product
+        assert_eq!(model_for("unknown").victim_losses(&swap(MINT, 250_000, 200_000), &[swap(MINT, 750_000, 300_000)]), Ok(vec![VictimLoss { input: 321_428, output: 128_571 }]));
+    }
+}
@@ -0,0 +1,73 @@
+use std::{collections::{BTreeMap, VecDeque}, env, net::SocketAddr, sync::{Arc, RwLock}};
+
+use axum::{extract::{ws::{Message, WebSocket}, State, WebSocketUpgrade}, response::IntoResponse, routing::get, Json, Router};
+use tokio::sync::broadcast;
+
+use crate::{cluster::EntityStats, detect::Sandwich, finder::ProcessContext};
+
+#[derive(Clone)]
+pub struct AppState {
+    pub message_history: Arc<RwLock<VecDeque<Sandwich>>>,
+    pub sender: broadcast::Sender<Sandwich>,
+    pub ctx: Arc<ProcessContext>,
+}
+
+async fn handle_websocket(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| handle_socket(socket, state))
+}
+
+async fn handle_socket(
+    mut socket: WebSocket,
+    state: AppState,
+) {
+    let mut receiver = state.sender.subscribe();
+    while let Ok(msg) = receiver.recv().await {
+        if socket.send(Message::Text(serde_json::to_string(&msg).unwrap().into())).await.is_err() {
+            break; // Client disconnected
+        }
+    }
+}
+
+async fn handle_history(State(state): State<AppState>) -> Json<Vec<Sandwich>> {
+    println!("history requested");
+    let snapshot = {
+        let history = state.message_history.try_read().unwrap();
+        history.iter().cloned().collect()
+    };
+    println!("history sent");
+    Json(snapshot)
+}
+
+async fn handle_sandwichers(State(state): State<AppState>) -> Json<Vec<EntityStats>> {
+    Json(state.ctx.clusters.lock().unwrap().stats())
+}
+
+async fn handle_skipped(State(state): State<AppState>) -> Json<BTreeMap<&'static str, u64>> {
+    Json(state.ctx.skipped.snapshot())
+}
+
+pub async fn start_web_server(sender: broadcast::Sender<Sandwich>, message_history: Arc<RwLock<VecDeque<Sandwich>>>, ctx: Arc<ProcessContext>) {
+    let app = Router::new()
+        .route("/", get(handle_websocket))
+        .route("/history", get(handle_history))
+        .route("/sandwichers", get(handle_sandwichers))
+        .route("/skipped", get(handle_skipped))
+        .with_state(AppState {
+            message_history,
+            sender,
+            ctx,
+        });
+    let api_port = env::var("API_PORT").unwrap_or_else(|_| "11000".to_string());
+    let listener = tokio::net::TcpListener::bind(format!("127.0.0.1:{api_port}"))
+        .await
+        .unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
+}
@@ -0,0 +1,228 @@
+use std::{collections::HashSet, time::Duration};
+
+use dashmap::DashMap;
+use futures::StreamExt;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_rpc_client_api::{client_error::{Error as ClientError, ErrorKind as ClientErrorKind}, config::RpcBlockConfig, custom_error::{JSON_RPC_SERVER_ERROR_LONG_TERM_STORAGE_SLOT_SKIPPED, JSON_RPC_SERVER_ERROR_SLOT_SKIPPED}, request::RpcError};
+use solana_sdk::{bs58, commitment_config::CommitmentConfig, message::VersionedMessage, pubkey::Pubkey, vote};
+use solana_transaction_status_client_types::{EncodedTransactionWithStatusMeta, TransactionDetails, UiConfirmedBlock, UiInnerInstructions, UiInstruction, UiTransactionEncoding, UiTransactionStatusMeta, UiTransactionTokenBalance};
+use tokio::sync::mpsc;
+use yellowstone_grpc_proto::prelude::{CompiledInstruction, InnerInstruction, InnerInstructions, Message, MessageAddressTableLookup, MessageHeader, SubscribeUpdateBlock, SubscribeUpdateTransactionInfo, TokenBalance, Transaction, TransactionError, TransactionStatusMeta, UiTokenAmount, UnixTimestamp};
+
+use crate::{detect::LeaderWindow, finder::{process_block, slot_statuses, ProcessContext}, storage::{DbMessage, SlotStatus}};
+
+const MAX_ATTEMPTS: usize = 3;
+
+fn convert_inner_instructions(inner_ixs: UiInnerInstructions) -> InnerInstructions {
+    InnerInstructions {
+        index: inner_ixs.index as u32,
+        instructions: inner_ixs.instructions.into_iter().filter_map(|ix| match ix {
+            UiInstruction::Compiled(ix) => Some(InnerInstruction {
+                program_id_index: ix.program_id_index as u32,
+                accounts: ix.accounts,
+                data: bs58::decode(ix.data).into_vec().ok()?,
+                stack_height: ix.stack_height,
+            }),
+            // only returned for jsonParsed encoding
+            UiInstruction::Parsed(_) => None,
+        }).collect(),
+    }
+}
+
+fn convert_token_balance(balance: UiTransactionTokenBalance) -> TokenBalance {
+    TokenBalance {
+        account_index: balance.account_index as u32,
+        mint: balance.mint,
+        ui_token_amount: Some(UiTokenAmount {
+            ui_amount: balance.ui_token_amount.ui_amount.unwrap_or_default(),
+            decimals: balance.ui_token_amount.decimals as u32,
+            amount: balance.ui_token_amount.amount,
+            ui_amount_string: balance.ui_token_amount.ui_amount_string,
+        }),
+        owner: Option::<String>::from(balance.owner).unwrap_or_default(),
+        program_id: Option::<String>::from(balance.program_id).unwrap_or_default(),
+    }
+}
+
+fn convert_meta(meta: UiTransactionStatusMeta) -> TransactionStatusMeta {
+    let loaded_addresses = Option::from(meta.loaded_addresses);
+    let (writable, readonly) = match loaded_addresses {
+        Some(loaded) => (loaded.writable, loaded.readonly),
+        None => (Vec::new(), Vec::new()),
+    };
+    let to_bytes = |keys: Vec<String>| keys.iter().filter_map(|key| key.parse::<Pubkey>().ok()).map(|key| key.to_bytes().to_vec()).collect();
+    let inner_instructions: Option<Vec<UiInnerInstructions>> = Option::from(meta.inner_instructions);
+    let log_messages: Option<Vec<String>> = Option::from(meta.log_messages);
+    let pre_token_balances: Option<Vec<UiTransactionTokenBalance>> = Option::from(meta.pre_token_balances);
+    let post_token_balances: Option<Vec<UiTransactionTokenBalance>> = Option::from(meta.post_token_balances);
+    TransactionStatusMeta {
+        err: meta.err.map(|err| TransactionError {
+            err: bincode::serialize(&err).unwrap_or_default(),
+        }),
+        fee: meta.fee,
+        pre_balances: meta.pre_balances,
+        post_balances: meta.post_balances,
+        inner_instructions_none: inner_instructions.is_none(),
+        inner_instructions: inner_instructions.unwrap_or_default().into_iter().map(convert_inner_instructions).collect(),
+        log_messages_none: log_messages.is_none(),
+        log_messages: log_messages.unwrap_or_default(),
+        pre_token_balances: pre_token_balances.unwrap_or_default().into_iter().map(convert_token_balance).collect(),
+        post_token_balances: post_token_balances.unwrap_or_default().into_iter().map(convert_token_balance).collect(),
+        loaded_writable_addresses: to_bytes(writable),
+        loaded_readonly_addresses: to_bytes(readonly),
+        compute_units_consumed: Option::from(meta.compute_units_consumed),
+        ..Default::default()
+    }
+}
+
+fn convert_transaction(index: usize, tx: EncodedTransactionWithStatusMeta) -> Option<SubscribeUpdateTransactionInfo> {
+    let versioned_tx = tx.transaction.decode()?;
+    let (header, account_keys, recent_blockhash, instructions, lookups, versioned) = match &versioned_tx.message {
+        VersionedMessage::Legacy(msg) => (msg.header, &msg.account_keys, msg.recent_blockhash, &msg.instructions, &[][..], false),
+        VersionedMessage::V0(msg) => (msg.header, &msg.account_keys, msg.recent_blockhash, &msg.instructions, &msg.address_table_lookups[..], true),
+    };
+    let is_vote = instructions.iter().any(|ix| account_keys.get(ix.program_id_index as usize) == Some(&vote::program::id()));
+    Some(SubscribeUpdateTransactionInfo {
+        signature: versioned_tx.signatures.first()?.as_ref().to_vec(),
+        is_vote,
+        transaction: Some(Transaction {
+            signatures: versioned_tx.signatures.iter().map(|sig| sig.as_ref().to_vec()).collect(),
+            message: Some(Message {
+                header: Some(MessageHeader {
+                    num_required_signatures: header.num_required_signatures as u32,
+                    num_readonly_signed_accounts: header.num_readonly_signed_accounts as u32,
+                    num_readonly_unsigned_accounts: header.num_readonly_unsigned_accounts as u32,
+                }),
+                account_keys: account_keys.iter().map(|key| key.to_bytes().to_vec()).collect(),
+                recent_blockhash: recent_blockhash.to_bytes().to_vec(),
+                instructions: instructions.iter().map(|ix| CompiledInstruction {
+                    program_id_index: ix.program_id_index as u32,
+                    accounts: ix.accounts.clone(),
+                    data: ix.data.clone(),
+                }).collect(),
+                versioned,
+                address_table_lookups: lookups.iter().map(|lookup| MessageAddressTableLookup {
+                    account_key: lookup.account_key.to_bytes().to_vec(),
+                    writable_indexes: lookup.writable_indexes.clone(),
+                    readonly_indexes: lookup.readonly_indexes.clone(),
+                }).collect(),
+            }),
+        }),
+        meta: tx.meta.map(convert_meta),
+        index: index as u64,
+    })
+}
+
+/// Converts a block returned by `getBlock` into the same structure streamed by yellowstone
+pub fn convert_block(slot: u64, block: UiConfirmedBlock) -> SubscribeUpdateBlock {
+    let transactions = block.transactions.unwrap_or_default().into_iter().enumerate().filter_map(|(i, tx)| convert_transaction(i, tx)).collect::<Vec<_>>();
+    SubscribeUpdateBlock {
+        slot,
+        blockhash: block.blockhash,
+        block_time: block.block_time.map(|timestamp| UnixTimestamp { timestamp }),
+        parent_slot: block.parent_slot,
+        parent_blockhash: block.previous_blockhash,
+        executed_transaction_count: transactions.len() as u64,
+        transactions,
+        ..Default::default()
+    }
+}
+
+fn is_skipped(e: &ClientError) -> bool {
+    match e.kind() {
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => *code == JSON_RPC_SERVER_ERROR_SLOT_SKIPPED || *code == JSON_RPC_SERVER_ERROR_LONG_TERM_STORAGE_SLOT_SKIPPED,
+        _ => false,
+    }
+}
+
+/// Fetches the block at `slot`, `None` if the slot was skipped
+pub async fn fetch_block(rpc_client: &RpcClient, slot: u64) -> Result<Option<SubscribeUpdateBlock>, ClientError> {
+    let config = RpcBlockConfig {
+        encoding: Some(UiTransactionEncoding::Base64),
+        transaction_details: Some(TransactionDetails::Full),
+        rewards: Some(false),
+        commitment: Some(CommitmentConfig::confirmed()),
+        max_supported_transaction_version: Some(0),
+    };
+    let mut attempt = 1;
+    loop {
+        match rpc_client.get_block_with_config(slot, config).await {
+            Ok(block) => return Ok(Some(convert_block(slot, block))),
+            Err(e) if is_skipped(&e) => return Ok(None),
+            Err(e) if attempt >= MAX_ATTEMPTS => return Err(e),
+            Err(e) => {
+                println!("unable to fetch block {slot} (attempt {attempt}): {e}");
+                tokio::time::sleep(Duration::from_secs(attempt as u64)).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Sends the message to the db writer, `false` once it's gone, i.e. on shutdown
+async fn send_to_db(db_sender: &mpsc::Sender<DbMessage>, msg: DbMessage) -> bool {
+    if db_sender.send(msg).await.is_err() {
+        println!("db channel closed, stopping the backfill");
+        return false;
+    }
+    true
+}
+
+/// Fetches the blocks in `[start, end]` over rpc and runs them through the same pipeline as the live finder.
+/// Slots in `done` (e.g. those already in the db) are skipped, which allows resuming an interrupted backfill.
+/// The status of every slot attempted is recorded, slots that couldn't be fetched are marked as missed.
+/// Stops early if the db writer goes away.
+pub async fn backfill(rpc_client: &RpcClient, start: u64, end: u64, done: &HashSet<u64>, concurrency: usize, ctx: &ProcessContext, db_sender: mpsc::Sender<DbMessage>) {
+    let lut_cache = DashMap::new();
+    let mut window = LeaderWindow::default();
+    let slots = (start..=end).filter(|slot| !done.contains(slot)).collect::<Vec<_>>();
+    println!("backfilling {} slots in [{start}, {end}]", slots.len());
+    let mut blocks = futures::stream::iter(slots).map(|slot| async move {
+        (slot, fetch_block(rpc_client, slot).await)
+    }).buffered(concurrency);
+    while let Some((slot, block)) = blocks.next().await {
+        let block = match block {
+            Ok(Some(block)) if block.block_time.is_some() => Some(block),
+            Ok(Some(_)) => {
+                println!("block {slot} has no block time");
+                None
+            }
+            Ok(None) => {
+                println!("slot {slot} skipped");
+                if !send_to_db(&db_sender, DbMessage::Slot(slot, SlotStatus::Skipped)).await {
+                    return;
+                }
+                continue;
+            }
+            Err(e) => {
+                println!("unable to fetch block {slot}: {e}");
+                None
+            }
+        };
+        let Some(block) = block else {
+            if !send_to_db(&db_sender, DbMessage::Slot(slot, SlotStatus::Missed)).await {
+                return;
+            }
+            continue;
+        };
+        let (db_block, sandwiches, swap_count) = match process_block(&block, Some(rpc_client), &lut_cache, ctx, &mut window).await {
+            Ok(processed) => processed,
+            Err(e) => {
+                println!("unable to process block {slot}: {e}");
+                if !send_to_db(&db_sender, DbMessage::Slot(slot, SlotStatus::Missed)).await {
+                    return;
+                }
+                continue;
+            }
+        };
+        println!("block {slot} backfilled, {} swaps found, {} bundles found", swap_count, sandwiches.len());
+        if !send_to_db(&db_sender, DbMessage::Block(db_block, sandwiches)).await {
+            return;
+        }
+        for (slot, status) in slot_statuses(&block) {
+            if !send_to_db(&db_sender, DbMessage::Slot(slot, status)).await {
+                return;
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use std::{collections::HashMap, env};
 
-use mysql::{prelude::Queryable, Pool};
+use sandwich_finder::storage::open_store;
 use solana_rpc_client::nonblocking::rpc_client::RpcClient;
 
 #[tokio::main]
@@ -16,28 +16,12 @@ async fn main() {
         });
         acc
     });
-    let mysql_url = env::var("MYSQL").unwrap();
-    let pool = Pool::new(mysql_url.as_str()).unwrap();
-    let mut conn = pool.get_conn().unwrap();
-    // insert in batches of 1600 rows
-    let stmt = "INSERT INTO leader_schedule (slot, leader) VALUES ";
-    let mut query = String::from(stmt);
-    let mut count = 0;
-    let mut cum_count = 0;
-    for (slot, leader) in rev_leader_schedule.iter() {
-        query.push_str(&format!("({}, '{}'),", slot, leader));
-        count += 1;
-        cum_count += 1;
-        if count == 1600 {
-            query.pop();
-            conn.exec_drop(query, ()).unwrap();
-            query = String::from(stmt);
-            count = 0;
-            println!("inserted {}/{}", cum_count, rev_leader_schedule.len());
+    // the store is blocking
+    tokio::task::block_in_place(|| {
+        let result = open_store().and_then(|mut store| store.insert_leader_schedule(&rev_leader_schedule));
+        if let Err(e) = result {
+            eprintln!("unable to store the leader schedule: {e}");
+            std::process::exit(1);
         }
-    }
-    if count > 0 {
-        query.pop();
-        conn.exec_drop(query, ()).unwrap();
-    }
-}
\ No newline at end of file
+    });
+}
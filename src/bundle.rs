@@ -0,0 +1,40 @@
+use std::{collections::HashMap, path::Path};
+
+use crate::{decode::config::load_file, detect::Sandwich};
+
+/// Looks up the jito bundle a transaction landed in, e.g. from a local block engine or bundle explorer mirror
+pub trait BundleSource: Send + Sync {
+    fn bundle_id(&self, sig: &str) -> Option<String>;
+}
+
+/// Bundles loaded from a TOML or JSON file mapping bundle ids to the signatures of their transactions,
+/// e.g. `"<bundle id>" = ["<sig>", "<sig>"]`
+#[derive(Default)]
+pub struct BundleFile {
+    bundles: HashMap<String, String>,
+}
+
+impl BundleFile {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let bundles: HashMap<String, Vec<String>> = load_file(path)?;
+        Ok(Self {
+            bundles: bundles.into_iter().flat_map(|(id, sigs)| sigs.into_iter().map(move |sig| (sig, id.clone()))).collect(),
+        })
+    }
+}
+
+impl BundleSource for BundleFile {
+    fn bundle_id(&self, sig: &str) -> Option<String> {
+        self.bundles.get(sig).cloned()
+    }
+}
+
+/// Tags the sandwiches as bundled if both legs landed in the same bundle, and unbundled otherwise
+pub fn tag_bundles(sandwiches: &mut [Sandwich], source: &dyn BundleSource) {
+    sandwiches.iter_mut().for_each(|sandwich| {
+        let frontrun = source.bundle_id(&sandwich.frontrun.sig);
+        let backrun = source.bundle_id(&sandwich.backrun.sig);
+        sandwich.bundled = Some(frontrun.is_some() && frontrun == backrun);
+        sandwich.bundle_id = frontrun.or(backrun);
+    });
+}
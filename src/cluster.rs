@@ -0,0 +1,245 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::{confidence::Funders, detect::Sandwich};
+
+/// Accounts funding more than this many accounts in a block are likely exchanges or other services rather than
+/// sandwichers spreading funds across their wallets, and aren't used to link wallets
+const MAX_FUNDED_ACCOUNTS: usize = 5;
+
+/// Activity of a sandwicher since startup
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityStats {
+    pub sandwicher_id: u64,
+    /// wallets and programs linked to the sandwicher
+    pub members: usize,
+    pub sandwiches: u64,
+    pub victims: u64,
+    pub profit_sol: f64,
+    pub loss_sol: f64,
+    pub first_slot: Option<u64>,
+    pub last_slot: Option<u64>,
+}
+
+impl EntityStats {
+    fn merge(&mut self, other: EntityStats) {
+        self.members += other.members;
+        self.sandwiches += other.sandwiches;
+        self.victims += other.victims;
+        self.profit_sol += other.profit_sol;
+        self.loss_sol += other.loss_sol;
+        self.first_slot = self.first_slot.into_iter().chain(other.first_slot).min();
+        self.last_slot = self.last_slot.into_iter().chain(other.last_slot).max();
+    }
+}
+
+/// Union-find over the wallets, token account owners and wrapper programs used in sandwiches, as well as the wallets
+/// funding the legs' fee payers, so that sandwichers rotating wallets are still seen as a single entity.
+/// Each entity is identified by the id of its earliest member, which only changes when it's linked to an older entity.
+#[derive(Default)]
+pub struct SandwicherClusters {
+    nodes: HashMap<String, usize>,
+    parent: Vec<usize>,
+    /// entity of each root
+    stats: HashMap<usize, EntityStats>,
+    next_id: u64,
+}
+
+impl SandwicherClusters {
+    /// Restores the clusters from (member, sandwicher id) pairs, a member seen with several ids links them together
+    pub fn with_members(members: Vec<(String, u64)>) -> Self {
+        let mut clusters = Self::default();
+        let mut roots: HashMap<u64, usize> = HashMap::new();
+        members.into_iter().for_each(|(member, id)| {
+            let node = clusters.node(&member, Some(id));
+            match roots.get(&id) {
+                Some(root) => {
+                    clusters.union(*root, node);
+                }
+                None => {
+                    roots.insert(id, node);
+                }
+            }
+        });
+        clusters
+    }
+
+    /// The node of the member, creating a new entity for it if it's unseen
+    fn node(&mut self, member: &str, id: Option<u64>) -> usize {
+        if let Some(node) = self.nodes.get(member) {
+            return *node;
+        }
+        let node = self.parent.len();
+        let id = id.unwrap_or(self.next_id);
+        self.next_id = self.next_id.max(id + 1);
+        self.parent.push(node);
+        self.nodes.insert(member.to_string(), node);
+        self.stats.insert(node, EntityStats {
+            sandwicher_id: id,
+            members: 1,
+            ..Default::default()
+        });
+        node
+    }
+
+    fn find(&mut self, node: usize) -> usize {
+        let mut root = node;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        // path compression
+        let mut node = node;
+        while self.parent[node] != root {
+            let next = self.parent[node];
+            self.parent[node] = root;
+            node = next;
+        }
+        root
+    }
+
+    /// Links the entities of the nodes, keeping the older id. Returns the id that was merged into it, if any.
+    fn union(&mut self, a: usize, b: usize) -> Option<u64> {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return None;
+        }
+        let (root, child) = if self.stats[&a].sandwicher_id <= self.stats[&b].sandwicher_id { (a, b) } else { (b, a) };
+        self.parent[child] = root;
+        let child_stats = self.stats.remove(&child).unwrap();
+        let merged_id = child_stats.sandwicher_id;
+        self.stats.get_mut(&root).unwrap().merge(child_stats);
+        Some(merged_id)
+    }
+
+    /// The wallets and programs linking the sandwich to its sandwicher
+    pub fn members(sandwich: &Sandwich, funders: &Funders) -> Vec<String> {
+        let mut members = HashSet::new();
+        [&sandwich.frontrun, &sandwich.backrun].iter().for_each(|leg| {
+            members.insert(leg.signer.clone());
+            members.insert(leg.subject.clone());
+            if let Some(outer_program) = &leg.outer_program {
+                members.insert(outer_program.clone());
+            }
+            if let Ok(signer) = leg.signer.parse() {
+                members.extend(funders.funders_of(&signer, MAX_FUNDED_ACCOUNTS).iter().map(|funder| funder.to_string()));
+            }
+        });
+        let mut members = members.into_iter().collect::<Vec<_>>();
+        members.sort();
+        members
+    }
+
+    /// Links the members of each sandwich and fills in its `sandwicher_id`, along with the ids of the entities it merged.
+    /// A sandwich matched by several rule sets only counts once in the stats.
+    pub fn assign(&mut self, sandwiches: &mut [Sandwich], funders: &Funders) {
+        let mut counted = HashSet::new();
+        sandwiches.iter_mut().for_each(|sandwich| {
+            let members = Self::members(sandwich, funders);
+            // ids from here on belong to members first seen in this sandwich, so no stored sandwich has them
+            let first_new_id = self.next_id;
+            let nodes = members.iter().map(|member| self.node(member, None)).collect::<Vec<_>>();
+            let merged_ids = nodes.windows(2).filter_map(|pair| self.union(pair[0], pair[1])).filter(|id| *id < first_new_id).collect();
+            let root = self.find(nodes[0]);
+            let stats = self.stats.get_mut(&root).unwrap();
+            if counted.insert((sandwich.frontrun.sig.clone(), sandwich.backrun.sig.clone())) {
+                stats.sandwiches += 1;
+                stats.victims += sandwich.victim.len() as u64;
+                stats.profit_sol += sandwich.profit_sol.unwrap_or_default();
+                stats.loss_sol += sandwich.loss_sol.unwrap_or_default();
+                stats.first_slot = Some(stats.first_slot.map_or(sandwich.slot, |slot| slot.min(sandwich.slot)));
+                stats.last_slot = Some(stats.last_slot.map_or(sandwich.slot, |slot| slot.max(sandwich.slot)));
+            }
+            sandwich.sandwicher_id = Some(stats.sandwicher_id);
+            sandwich.sandwicher_members = members;
+            sandwich.merged_sandwicher_ids = merged_ids;
+        });
+    }
+
+    /// Stats of the entities that sandwiched since startup, most active first
+    pub fn stats(&self) -> Vec<EntityStats> {
+        let mut stats = self.stats.values().filter(|stats| stats.sandwiches > 0).cloned().collect::<Vec<_>>();
+        stats.sort_by(|a, b| b.sandwiches.cmp(&a.sandwiches).then(a.sandwicher_id.cmp(&b.sandwicher_id)));
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode::{fixtures::{swap, MINT_A, MINT_B}, Swap}, rules::RuleSet};
+
+    fn leg(wallet: &str) -> Swap {
+        Swap {
+            signer: wallet.to_string(),
+            subject: wallet.to_string(),
+            ..swap(MINT_A, MINT_B, 100, 100)
+        }
+    }
+
+    /// A sandwich whose only members are the wallets of its legs
+    fn sandwich(slot: u64, frontrun: &str, backrun: &str) -> Sandwich {
+        Sandwich::new(slot, leg(frontrun), vec![leg("victim")], leg(backrun), 0, &RuleSet::default()).unwrap()
+    }
+
+    fn assign(clusters: &mut SandwicherClusters, mut sandwich: Sandwich) -> Sandwich {
+        clusters.assign(std::slice::from_mut(&mut sandwich), &Funders::new(&[]));
+        sandwich
+    }
+
+    #[test]
+    fn finds_roots() {
+        let mut clusters = SandwicherClusters::default();
+        let nodes = ["a", "b", "c", "d"].map(|member| clusters.node(member, None));
+        assert_eq!(clusters.union(nodes[2], nodes[3]), Some(3));
+        assert_eq!(clusters.union(nodes[1], nodes[2]), Some(2));
+        assert_eq!(clusters.union(nodes[3], nodes[1]), None);
+        assert!(nodes[1..].iter().all(|node| clusters.find(*node) == nodes[1]));
+        assert_eq!(clusters.find(nodes[0]), nodes[0]);
+        assert_eq!(clusters.stats[&nodes[1]].members, 3);
+    }
+
+    #[test]
+    fn merges_entities_sharing_a_member() {
+        let mut clusters = SandwicherClusters::default();
+        let first = assign(&mut clusters, sandwich(10, "a", "b"));
+        assert_eq!(first.sandwicher_id, Some(0));
+        assert_eq!(first.sandwicher_members, vec!["a", "b"]);
+        // linking new members to an entity doesn't merge any stored one
+        assert!(first.merged_sandwicher_ids.is_empty());
+        let second = assign(&mut clusters, sandwich(11, "c", "d"));
+        assert_eq!((second.sandwicher_id, second.merged_sandwicher_ids.as_slice()), (Some(2), &[][..]));
+        // b and d link both entities, which keep the older id
+        let third = assign(&mut clusters, sandwich(12, "b", "d"));
+        assert_eq!((third.sandwicher_id, third.merged_sandwicher_ids.as_slice()), (Some(0), &[2][..]));
+        let stats = clusters.stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!((stats[0].sandwicher_id, stats[0].members, stats[0].sandwiches, stats[0].victims), (0, 4, 3, 3));
+        assert_eq!((stats[0].first_slot, stats[0].last_slot), (Some(10), Some(12)));
+    }
+
+    #[test]
+    fn counts_sandwiches_of_several_rule_sets_once() {
+        let mut clusters = SandwicherClusters::default();
+        let mut strict = sandwich(10, "a", "b");
+        strict.rule_set = "strict".to_string();
+        let mut sandwiches = vec![sandwich(10, "a", "b"), strict];
+        clusters.assign(&mut sandwiches, &Funders::new(&[]));
+        assert!(sandwiches.iter().all(|sandwich| sandwich.sandwicher_id == Some(0)));
+        let stats = clusters.stats();
+        assert_eq!((stats[0].sandwiches, stats[0].victims), (1, 1));
+    }
+
+    #[test]
+    fn restores_entities_from_members() {
+        let members = [("a", 3), ("b", 3), ("b", 5), ("c", 5), ("d", 7)];
+        let mut clusters = SandwicherClusters::with_members(members.iter().map(|(member, id)| (member.to_string(), *id)).collect());
+        let linked = assign(&mut clusters, sandwich(10, "c", "x"));
+        assert_eq!((linked.sandwicher_id, linked.merged_sandwicher_ids.as_slice()), (Some(3), &[][..]));
+        let merged = assign(&mut clusters, sandwich(11, "d", "a"));
+        assert_eq!((merged.sandwicher_id, merged.merged_sandwicher_ids.as_slice()), (Some(3), &[7][..]));
+        // new entities don't reuse restored ids
+        assert_eq!(assign(&mut clusters, sandwich(12, "y", "z")).sandwicher_id, Some(9));
+    }
+}
@@ -0,0 +1,54 @@
+use std::{io::{self, Read}, marker::PhantomData};
+
+use prost::Message;
+
+/// Reads the varint length prefix of the next message, `None` on a clean EOF
+fn read_length<R: Read>(reader: &mut R) -> io::Result<Option<usize>> {
+    let mut len: u64 = 0;
+    for i in 0..10 {
+        let mut byte = [0u8; 1];
+        if reader.read(&mut byte)? == 0 {
+            if i == 0 {
+                return Ok(None);
+            }
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated length prefix"));
+        }
+        len |= ((byte[0] & 0x7f) as u64) << (7 * i);
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(len as usize));
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::InvalidData, "length prefix too long"))
+}
+
+/// Iterates over length-delimited protobuf messages, as written by `prost::Message::encode_length_delimited`
+pub struct DelimitedReader<R, M> {
+    reader: R,
+    _message: PhantomData<M>,
+}
+
+impl<R: Read, M: Message + Default> DelimitedReader<R, M> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            _message: PhantomData,
+        }
+    }
+}
+
+impl<R: Read, M: Message + Default> Iterator for DelimitedReader<R, M> {
+    type Item = io::Result<M>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let len = match read_length(&mut self.reader) {
+            Ok(Some(len)) => len,
+            Ok(None) => return None,
+            Err(e) => return Some(Err(e)),
+        };
+        let mut buf = vec![0u8; len];
+        if let Err(e) = self.reader.read_exact(&mut buf) {
+            return Some(Err(e));
+        }
+        Some(M::decode(buf.as_slice()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)))
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{decode::DecompiledTransaction, detect::Sandwich};
+
+// weights of the signals, summing up to 1
+const SAME_WALLET_WEIGHT: f64 = 0.25;
+const SYMMETRY_WEIGHT: f64 = 0.2;
+const VICTIM_COUNT_WEIGHT: f64 = 0.1;
+const ADJACENCY_WEIGHT: f64 = 0.2;
+const FUNDING_WEIGHT: f64 = 0.1;
+const TIP_WEIGHT: f64 = 0.15;
+
+/// Victim counts up to this are typical, more victims get increasingly likely to be unrelated swaps
+const TYPICAL_VICTIMS: usize = 3;
+
+/// Signals of how likely a detected sandwich is an actual one, each within [0, 1]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Signals {
+    /// 1 if the legs have the same signer, 0.75 if only the same subject
+    pub same_wallet: f64,
+    /// how close the amount sold back in the backrun is to the amount bought in the frontrun
+    pub symmetry: f64,
+    pub victim_count: f64,
+    /// how few unrelated transactions landed between the legs and the victims, legs in different slots aren't penalised
+    pub adjacency: f64,
+    /// 1 if the legs' fee payers are the same, funded one another or share a funder in the transactions seen
+    pub shared_funding: f64,
+    /// 1 if either leg paid a jito tip, see `Sandwich::frontrun_tip`
+    pub tip: f64,
+}
+
+impl Signals {
+    pub fn new(sandwich: &Sandwich, payers: (Option<&Pubkey>, Option<&Pubkey>), funders: &Funders) -> Self {
+        let (frontrun, backrun) = (&sandwich.frontrun, &sandwich.backrun);
+        let same_wallet = if frontrun.signer == backrun.signer {
+            1.0
+        } else if frontrun.subject == backrun.subject {
+            0.75
+        } else {
+            0.0
+        };
+        let (bought, sold) = (frontrun.output_amount, backrun.input_amount);
+        let symmetry = if bought.max(sold) == 0 {
+            0.0
+        } else {
+            bought.min(sold) as f64 / bought.max(sold) as f64
+        };
+        let victim_count = TYPICAL_VICTIMS as f64 / sandwich.victim.len().max(TYPICAL_VICTIMS) as f64;
+        // txs between consecutive swaps of the sandwich that aren't part of it
+        let mut swaps = vec![frontrun];
+        swaps.extend(sandwich.victim.iter());
+        swaps.push(backrun);
+        let gaps = swaps.windows(2).filter(|pair| pair[0].slot == pair[1].slot).map(|pair| pair[1].order.saturating_sub(pair[0].order + 1)).sum::<u64>();
+        let adjacency = 1.0 / (1 + gaps) as f64;
+        let shared_funding = match payers {
+            (Some(a), Some(b)) if funders.related(a, b) => 1.0,
+            _ => 0.0,
+        };
+        let tip = if sandwich.profit.tips > 0 { 1.0 } else { 0.0 };
+        Self {
+            same_wallet,
+            symmetry,
+            victim_count,
+            adjacency,
+            shared_funding,
+            tip,
+        }
+    }
+
+    /// Weighted sum of the signals within [0, 1]
+    pub fn score(&self) -> f64 {
+        self.same_wallet * SAME_WALLET_WEIGHT
+            + self.symmetry * SYMMETRY_WEIGHT
+            + self.victim_count * VICTIM_COUNT_WEIGHT
+            + self.adjacency * ADJACENCY_WEIGHT
+            + self.shared_funding * FUNDING_WEIGHT
+            + self.tip * TIP_WEIGHT
+    }
+}
+
+/// Senders of sol transfers to each account among the transactions seen
+#[derive(Default)]
+pub struct Funders {
+    funders: HashMap<Pubkey, HashSet<Pubkey>>,
+}
+
+impl Funders {
+    pub fn new(txs: &[&DecompiledTransaction]) -> Self {
+        let mut funders: HashMap<Pubkey, HashSet<Pubkey>> = HashMap::new();
+        txs.iter().flat_map(|tx| tx.transfers.iter()).filter(|(from, to, lamports)| from != to && *lamports > 0).for_each(|(from, to, _)| {
+            funders.entry(*to).or_default().insert(*from);
+        });
+        Self { funders }
+    }
+
+    /// Senders of sol to the account, leaving out those that funded more than `max_funded` accounts
+    pub fn funders_of(&self, account: &Pubkey, max_funded: usize) -> Vec<&Pubkey> {
+        let Some(funders) = self.funders.get(account) else {
+            return Vec::new();
+        };
+        funders.iter().filter(|funder| self.funders.values().filter(|funders| funders.contains(funder)).count() <= max_funded).collect()
+    }
+
+    /// Whether the accounts are the same, one funded the other, or they share a funder
+    pub fn related(&self, a: &Pubkey, b: &Pubkey) -> bool {
+        if a == b {
+            return true;
+        }
+        let (fa, fb) = (self.funders.get(a), self.funders.get(b));
+        fa.is_some_and(|fa| fa.contains(b)) || fb.is_some_and(|fb| fb.contains(a)) || fa.zip(fb).is_some_and(|(fa, fb)| !fa.is_disjoint(fb))
+    }
+}
+
+/// Fills in the confidence of the sandwiches found among `txs`
+pub fn score_sandwiches(sandwiches: &mut [Sandwich], txs: &[&DecompiledTransaction]) {
+    let funders = Funders::new(txs);
+    let payers = txs.iter().map(|tx| (tx.sig.as_str(), &tx.payer)).collect::<HashMap<_, _>>();
+    sandwiches.iter_mut().for_each(|sandwich| {
+        let legs = (payers.get(sandwich.frontrun.sig.as_str()).copied(), payers.get(sandwich.backrun.sig.as_str()).copied());
+        let signals = Signals::new(sandwich, legs, &funders);
+        sandwich.confidence = signals.score();
+        sandwich.signals = Some(signals);
+    });
+}
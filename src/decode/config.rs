@@ -0,0 +1,218 @@
+use std::{collections::HashSet, fs, path::Path, str::FromStr};
+
+use serde::{de::DeserializeOwned, Deserialize};
+use solana_sdk::pubkey::Pubkey;
+
+use super::{LayoutDecoder, SwapDecoder, SwapLayout};
+
+/// DEX definitions loaded from a TOML or JSON file, e.g.
+/// ```toml
+/// [[dex]]
+/// name = "whirlpool"
+/// program_id = "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc"
+///
+/// [[dex.layouts]]
+/// discriminator = "f8c69e91e17587c8"
+/// amm_index = 2
+/// send_ix_index = 1
+/// recv_ix_index = 2
+/// data_len = 42
+/// ```
+/// A definition sharing its name with a built-in decoder replaces it, otherwise it's added to the registry. A definition for a
+/// program that's already decoded under another name is rejected.
+#[derive(Deserialize)]
+pub struct DexConfig {
+    #[serde(default)]
+    pub dex: Vec<DexDefinition>,
+}
+
+#[derive(Deserialize)]
+pub struct DexDefinition {
+    pub name: String,
+    pub program_id: String,
+    pub layouts: Vec<LayoutDefinition>,
+}
+
+#[derive(Deserialize)]
+pub struct LayoutDefinition {
+    /// hex encoded, e.g. "09" or "f8c69e91e17587c8"
+    pub discriminator: String,
+    pub amm_index: usize,
+    pub send_ix_index: usize,
+    pub recv_ix_index: usize,
+    pub data_len: usize,
+}
+
+/// Parses the file as JSON if it has a `.json` extension, TOML otherwise
+pub fn load_file<T: DeserializeOwned>(path: &Path) -> Result<T, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("unable to read {}: {e}", path.display()))?;
+    if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::from_str(&content).map_err(|e| format!("unable to parse {}: {e}", path.display()))
+    } else {
+        toml::from_str(&content).map_err(|e| format!("unable to parse {}: {e}", path.display()))
+    }
+}
+
+fn decode_hex(s: &str) -> Result<Vec<u8>, String> {
+    let s = s.trim_start_matches("0x");
+    if s.len() % 2 != 0 {
+        return Err(format!("odd number of hex digits in \"{s}\""));
+    }
+    (0..s.len()).step_by(2).map(|i| {
+        u8::from_str_radix(&s[i..i + 2], 16).map_err(|_| format!("invalid hex digits in \"{s}\""))
+    }).collect()
+}
+
+impl LayoutDefinition {
+    pub fn validate(&self) -> Result<SwapLayout, String> {
+        let discriminant = decode_hex(&self.discriminator)?;
+        if discriminant.is_empty() {
+            return Err("discriminator must not be empty".to_string());
+        }
+        if self.data_len < discriminant.len() {
+            return Err(format!("data_len {} is shorter than the discriminator", self.data_len));
+        }
+        // direct calls look up the transfers at index - 1
+        if self.send_ix_index == 0 || self.recv_ix_index == 0 {
+            return Err("send_ix_index and recv_ix_index must be at least 1".to_string());
+        }
+        if self.send_ix_index == self.recv_ix_index {
+            return Err("send_ix_index and recv_ix_index must differ".to_string());
+        }
+        Ok(SwapLayout {
+            discriminant,
+            amm_index: self.amm_index,
+            send_ix_index: self.send_ix_index,
+            recv_ix_index: self.recv_ix_index,
+            data_len: self.data_len,
+        })
+    }
+}
+
+impl DexDefinition {
+    pub fn validate(&self) -> Result<LayoutDecoder, String> {
+        if self.name.is_empty() {
+            return Err("dex name must not be empty".to_string());
+        }
+        let program_id = Pubkey::from_str(&self.program_id).map_err(|e| format!("{}: invalid program_id: {e}", self.name))?;
+        if self.layouts.is_empty() {
+            return Err(format!("{}: at least one layout is required", self.name));
+        }
+        let layouts = self.layouts.iter().enumerate().map(|(i, layout)| {
+            layout.validate().map_err(|e| format!("{}: layout #{i}: {e}", self.name))
+        }).collect::<Result<Vec<_>, _>>()?;
+        Ok(LayoutDecoder::new(&self.name, program_id, layouts))
+    }
+}
+
+impl DexConfig {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        load_file(path)
+    }
+
+    pub fn validate(&self) -> Result<Vec<LayoutDecoder>, String> {
+        let mut names = HashSet::new();
+        let mut program_ids = HashSet::new();
+        self.dex.iter().map(|dex| {
+            if !names.insert(dex.name.as_str()) {
+                return Err(format!("{}: duplicate dex name", dex.name));
+            }
+            let decoder = dex.validate()?;
+            if !program_ids.insert(decoder.program_id()) {
+                return Err(format!("{}: program {} is defined more than once", dex.name, decoder.program_id()));
+            }
+            Ok(decoder)
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::{DecoderRegistry, WHIRLPOOL_PUBKEY};
+
+    fn layout() -> LayoutDefinition {
+        LayoutDefinition {
+            discriminator: "f8c69e91e17587c8".to_string(),
+            amm_index: 2,
+            send_ix_index: 1,
+            recv_ix_index: 2,
+            data_len: 42,
+        }
+    }
+
+    fn dex(name: &str, program_id: &str) -> DexDefinition {
+        DexDefinition {
+            name: name.to_string(),
+            program_id: program_id.to_string(),
+            layouts: vec![layout()],
+        }
+    }
+
+    const PROGRAM_ID: &str = "11111111111111111111111111111112";
+
+    #[test]
+    fn accepts_valid_layout() {
+        let layout = layout().validate().unwrap();
+        assert_eq!(layout.discriminant, vec![0xf8, 0xc6, 0x9e, 0x91, 0xe1, 0x75, 0x87, 0xc8]);
+        assert_eq!((layout.amm_index, layout.send_ix_index, layout.recv_ix_index, layout.data_len), (2, 1, 2, 42));
+    }
+
+    #[test]
+    fn rejects_invalid_discriminator() {
+        for discriminator in ["", "0x", "f8c", "zz"] {
+            let layout = LayoutDefinition { discriminator: discriminator.to_string(), ..layout() };
+            assert!(layout.validate().is_err(), "{discriminator}");
+        }
+    }
+
+    #[test]
+    fn rejects_data_len_shorter_than_discriminator() {
+        let layout = LayoutDefinition { data_len: 7, ..layout() };
+        assert!(layout.validate().unwrap_err().contains("data_len 7"));
+    }
+
+    #[test]
+    fn rejects_invalid_transfer_indices() {
+        assert!(LayoutDefinition { send_ix_index: 0, ..layout() }.validate().is_err());
+        assert!(LayoutDefinition { recv_ix_index: 0, ..layout() }.validate().is_err());
+        assert!(LayoutDefinition { send_ix_index: 2, ..layout() }.validate().is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_dex() {
+        assert!(dex("", PROGRAM_ID).validate().is_err());
+        assert!(dex("dex", "not base58!").validate().unwrap_err().contains("invalid program_id"));
+        assert!(DexDefinition { layouts: Vec::new(), ..dex("dex", PROGRAM_ID) }.validate().is_err());
+        let bad_layout = DexDefinition { layouts: vec![LayoutDefinition { data_len: 1, ..layout() }], ..dex("dex", PROGRAM_ID) };
+        assert!(bad_layout.validate().unwrap_err().starts_with("dex: layout #0"));
+    }
+
+    #[test]
+    fn rejects_duplicates() {
+        let config = DexConfig { dex: vec![dex("dex", PROGRAM_ID), dex("dex", "11111111111111111111111111111113")] };
+        assert!(config.validate().unwrap_err().contains("duplicate dex name"));
+        let config = DexConfig { dex: vec![dex("a", PROGRAM_ID), dex("b", PROGRAM_ID)] };
+        assert!(config.validate().unwrap_err().contains("defined more than once"));
+    }
+
+    #[test]
+    fn overrides_built_in_by_name() {
+        let mut registry = DecoderRegistry::default();
+        let count = registry.decoders().len();
+        let mut whirlpool = dex("whirlpool", &WHIRLPOOL_PUBKEY.to_string());
+        whirlpool.layouts[0].data_len = 50;
+        registry.merge_config(&DexConfig { dex: vec![whirlpool, dex("new_dex", PROGRAM_ID)] }).unwrap();
+        assert_eq!(registry.decoders().len(), count + 1);
+        let whirlpool = registry.decoders().iter().find(|decoder| decoder.name() == "whirlpool").unwrap();
+        assert_eq!(whirlpool.layouts()[0].data_len, 50);
+        assert!(registry.decoders().iter().any(|decoder| decoder.name() == "new_dex"));
+    }
+
+    #[test]
+    fn rejects_built_in_program_under_another_name() {
+        let mut registry = DecoderRegistry::default();
+        let config = DexConfig { dex: vec![dex("my_whirlpool", &WHIRLPOOL_PUBKEY.to_string())] };
+        assert!(registry.merge_config(&config).unwrap_err().contains("already decoded by whirlpool"));
+    }
+}
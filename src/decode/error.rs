@@ -0,0 +1,55 @@
+use std::fmt::Display;
+
+use solana_sdk::pubkey::Pubkey;
+
+/// Why a transaction couldn't be decoded, the transaction is skipped but the rest of the block is still processed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// a key that isn't 32 bytes or a mint that doesn't parse
+    InvalidPubkey(String),
+    /// the lut isn't cached and couldn't be fetched, e.g. offline or it's been closed
+    LutNotFound(Pubkey),
+    LutIndexOutOfRange { lut: Pubkey, index: u8 },
+    InvalidLut(Pubkey),
+    /// fetching luts failed
+    Rpc(String),
+    /// an instruction refers to an account beyond the tx's account keys
+    AccountIndexOutOfRange(usize),
+    /// the transfers of a swap aren't where its layout says they are
+    InnerInstructionOutOfRange { program: Pubkey, index: usize },
+    /// a transfer without enough accounts or data
+    MalformedTransfer,
+}
+
+impl DecodeError {
+    /// Short name of the error used to count skipped transactions
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::InvalidPubkey(_) => "invalid_pubkey",
+            Self::LutNotFound(_) => "lut_not_found",
+            Self::LutIndexOutOfRange { .. } => "lut_index_out_of_range",
+            Self::InvalidLut(_) => "invalid_lut",
+            Self::Rpc(_) => "rpc",
+            Self::AccountIndexOutOfRange(_) => "account_index_out_of_range",
+            Self::InnerInstructionOutOfRange { .. } => "inner_instruction_out_of_range",
+            Self::MalformedTransfer => "malformed_transfer",
+        }
+    }
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidPubkey(key) => write!(f, "invalid pubkey {key}"),
+            Self::LutNotFound(lut) => write!(f, "lut {lut} not found"),
+            Self::LutIndexOutOfRange { lut, index } => write!(f, "index {index} out of range of lut {lut}"),
+            Self::InvalidLut(lut) => write!(f, "unable to deserialize lut {lut}"),
+            Self::Rpc(e) => write!(f, "rpc error: {e}"),
+            Self::AccountIndexOutOfRange(index) => write!(f, "account index {index} out of range"),
+            Self::InnerInstructionOutOfRange { program, index } => write!(f, "inner instruction {index} of {program} out of range"),
+            Self::MalformedTransfer => write!(f, "malformed transfer"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
@@ -0,0 +1,163 @@
+//! Minimal transactions for the decoder tests.
+//! Accounts are by index into `keys`: 0 signer, 1 user token A, 2 pool token A, 3 pool token B, 4 user token B,
+//! 5 amm, 6 token program, 7 the program being decoded, 8 an aggregator calling it, 9 system program.
+
+use solana_sdk::{instruction::{AccountMeta, Instruction}, pubkey::Pubkey};
+use yellowstone_grpc_proto::prelude::{InnerInstruction, InnerInstructions, TokenBalance, TransactionStatusMeta, UiTokenAmount};
+
+use super::{DecodeContext, Swap, SwapDecoder};
+
+pub const SIGNER: u8 = 0;
+pub const USER_A: u8 = 1;
+pub const POOL_A: u8 = 2;
+pub const POOL_B: u8 = 3;
+pub const USER_B: u8 = 4;
+pub const AMM: u8 = 5;
+pub const TOKEN_PROGRAM: u8 = 6;
+pub const PROGRAM: u8 = 7;
+pub const AGGREGATOR: u8 = 8;
+pub const SYSTEM_PROGRAM: u8 = 9;
+
+pub const MINT_A: Pubkey = Pubkey::from_str_const("EPjFWdd5AufqSSqeM2qFtw5Ux8tKoRRwoVwPKsnUk3ii");
+pub const MINT_B: Pubkey = Pubkey::from_str_const("DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263");
+
+/// The account keys of the transaction, with the decoded program at `PROGRAM`
+pub fn keys(program_id: Pubkey) -> Vec<Pubkey> {
+    let mut keys = (0..10).map(|i| Pubkey::new_from_array([i + 1; 32])).collect::<Vec<_>>();
+    keys[TOKEN_PROGRAM as usize] = Pubkey::from_str_const("TokenkegQfeZyiNwAJbNbGFMaFYpFSiYi8EGwd4sPuQ");
+    keys[PROGRAM as usize] = program_id;
+    keys[SYSTEM_PROGRAM as usize] = solana_sdk::system_program::ID;
+    keys
+}
+
+/// Key of the account at the index
+pub fn key(program_id: Pubkey, index: u8) -> String {
+    keys(program_id)[index as usize].to_string()
+}
+
+/// Token `transfer` of `amount`
+pub fn transfer(source: u8, destination: u8, authority: u8, amount: u64) -> InnerInstruction {
+    let mut data = vec![3];
+    data.extend(amount.to_le_bytes());
+    InnerInstruction {
+        program_id_index: TOKEN_PROGRAM as u32,
+        accounts: vec![source, destination, authority],
+        data,
+        ..Default::default()
+    }
+}
+
+/// System program transfer of `lamports`
+pub fn sol_transfer(from: u8, to: u8, lamports: u64) -> InnerInstruction {
+    let mut data = vec![2, 0, 0, 0];
+    data.extend(lamports.to_le_bytes());
+    InnerInstruction {
+        program_id_index: SYSTEM_PROGRAM as u32,
+        accounts: vec![from, to],
+        data,
+        ..Default::default()
+    }
+}
+
+/// An inner instruction that isn't a transfer, e.g. a vault deposit or an lp mint
+pub fn other() -> InnerInstruction {
+    InnerInstruction {
+        program_id_index: TOKEN_PROGRAM as u32,
+        accounts: vec![AMM],
+        data: vec![0xaa],
+        ..Default::default()
+    }
+}
+
+/// A cpi into the decoded program
+pub fn program_call(accounts: Vec<u8>, data: Vec<u8>) -> InnerInstruction {
+    InnerInstruction {
+        program_id_index: PROGRAM as u32,
+        accounts,
+        data,
+        ..Default::default()
+    }
+}
+
+/// Instruction data of `len` bytes starting with the discriminant
+pub fn ix_data(discriminant: &[u8], len: usize) -> Vec<u8> {
+    let mut data = discriminant.to_vec();
+    data.resize(len, 0);
+    data
+}
+
+fn balance(account_index: u8, mint: &Pubkey, decimals: u32) -> TokenBalance {
+    TokenBalance {
+        account_index: account_index as u32,
+        mint: mint.to_string(),
+        ui_token_amount: Some(UiTokenAmount {
+            decimals,
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Token A has 6 decimals and token B 9
+pub fn meta() -> TransactionStatusMeta {
+    TransactionStatusMeta {
+        post_token_balances: vec![balance(USER_A, &MINT_A, 6), balance(POOL_A, &MINT_A, 6), balance(POOL_B, &MINT_B, 9), balance(USER_B, &MINT_B, 9)],
+        ..Default::default()
+    }
+}
+
+/// Runs the decoder against the top level instruction `ix` (calling `program` with `accounts`) and its inner instructions
+pub fn decode(decoder: &dyn SwapDecoder, program: u8, accounts: &[u8], data: Vec<u8>, inner: Vec<InnerInstruction>) -> Vec<Swap> {
+    let keys = keys(decoder.program_id());
+    let meta = meta();
+    let ix = Instruction {
+        program_id: keys[program as usize],
+        accounts: accounts.iter().map(|i| AccountMeta::new(keys[*i as usize], false)).collect(),
+        data,
+    };
+    let inner_ix = InnerInstructions {
+        index: 0,
+        instructions: inner,
+    };
+    let ctx = DecodeContext {
+        meta: &meta,
+        account_keys: &keys,
+        sig: "sig",
+        slot: 1,
+        tx_index: 0,
+    };
+    decoder.decode(&ix, &inner_ix, &ctx).unwrap()
+}
+
+/// A swap with only the mints and amounts set, for tests outside the decoders
+pub fn swap(input_mint: Pubkey, output_mint: Pubkey, input_amount: u64, output_amount: u64) -> Swap {
+    Swap {
+        outer_program: None,
+        program: String::new(),
+        amm: String::new(),
+        signer: String::new(),
+        subject: String::new(),
+        input_mint: input_mint.to_string(),
+        output_mint: output_mint.to_string(),
+        input_amount,
+        output_amount,
+        slot: 0,
+        order: 0,
+        sig: String::new(),
+        input_decimals: None,
+        output_decimals: None,
+        input_ui_amount: None,
+        output_ui_amount: None,
+        input_symbol: None,
+        output_symbol: None,
+    }
+}
+
+/// Checks the amm, subject, mints and amounts of the swap against the accounts of `keys`
+pub fn assert_swap(swap: &Swap, program_id: Pubkey, input: (Pubkey, u64), output: (Pubkey, u64)) {
+    assert_eq!(swap.program, program_id.to_string());
+    assert_eq!(swap.amm, key(program_id, AMM));
+    assert_eq!(swap.subject, key(program_id, SIGNER));
+    assert_eq!((swap.input_mint.as_str(), swap.input_amount), (input.0.to_string().as_str(), input.1));
+    assert_eq!((swap.output_mint.as_str(), swap.output_amount), (output.0.to_string().as_str(), output.1));
+}
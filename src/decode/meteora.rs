@@ -0,0 +1,71 @@
+use solana_sdk::pubkey::Pubkey;
+
+use super::{LayoutDecoder, SwapLayout};
+
+pub const DLMM_PUBKEY: Pubkey = Pubkey::from_str_const("LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo");
+pub const METEORA_PUBKEY: Pubkey = Pubkey::from_str_const("Eo7WjKq67rjJQSZxS6z3YkapzY3eMj6Xy8X5EQVn5UaB");
+
+const SWAP_DISCRIMINANT: [u8; 8] = [0xf8, 0xc6, 0x9e, 0x91, 0xe1, 0x75, 0x87, 0xc8];
+
+/// Meteora DLMM
+pub fn meteora_dlmm_decoder() -> LayoutDecoder {
+    LayoutDecoder::new("meteora_dlmm", DLMM_PUBKEY, vec![
+        SwapLayout::new(&SWAP_DISCRIMINANT, 0, 1, 2, 24),
+    ])
+}
+
+/// Meteora dynamic AMM
+pub fn meteora_decoder() -> LayoutDecoder {
+    LayoutDecoder::new("meteora", METEORA_PUBKEY, vec![
+        // swap, (charge_fee), deposit, send, mint_lp, withdraw, recv, burn_lp
+        SwapLayout::new(&SWAP_DISCRIMINANT, 0, 2, 5, 24),
+        SwapLayout::new(&SWAP_DISCRIMINANT, 0, 3, 6, 24),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::fixtures::*;
+
+    #[test]
+    fn decodes_dlmm_swap() {
+        let swaps = decode(&meteora_dlmm_decoder(), PROGRAM, &[AMM], ix_data(&SWAP_DISCRIMINANT, 24), vec![
+            transfer(USER_A, POOL_A, SIGNER, 100),
+            transfer(POOL_B, USER_B, AMM, 250),
+        ]);
+        assert_eq!(swaps.len(), 1);
+        assert_swap(&swaps[0], DLMM_PUBKEY, (MINT_A, 100), (MINT_B, 250));
+    }
+
+    #[test]
+    fn decodes_dynamic_amm_swap() {
+        // deposit, send, mint_lp, withdraw, recv, burn_lp
+        let swaps = decode(&meteora_decoder(), PROGRAM, &[AMM], ix_data(&SWAP_DISCRIMINANT, 24), vec![
+            other(),
+            transfer(USER_A, POOL_A, SIGNER, 100),
+            other(),
+            other(),
+            transfer(POOL_B, USER_B, AMM, 250),
+            other(),
+        ]);
+        assert_eq!(swaps.len(), 1);
+        assert_swap(&swaps[0], METEORA_PUBKEY, (MINT_A, 100), (MINT_B, 250));
+    }
+
+    #[test]
+    fn decodes_dynamic_amm_swap_with_fee() {
+        // charge_fee, deposit, send, mint_lp, withdraw, recv, burn_lp
+        let swaps = decode(&meteora_decoder(), PROGRAM, &[AMM], ix_data(&SWAP_DISCRIMINANT, 24), vec![
+            other(),
+            other(),
+            transfer(USER_A, POOL_A, SIGNER, 100),
+            other(),
+            other(),
+            transfer(POOL_B, USER_B, AMM, 250),
+            other(),
+        ]);
+        assert_eq!(swaps.len(), 1);
+        assert_swap(&swaps[0], METEORA_PUBKEY, (MINT_A, 100), (MINT_B, 250));
+    }
+}
@@ -0,0 +1,573 @@
+use std::{collections::HashMap, fmt::Debug, path::Path, str::FromStr};
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{account::ReadableAccount, address_lookup_table::{state::AddressLookupTable, AddressLookupTableAccount}, bs58, instruction::{AccountMeta, Instruction}, pubkey::Pubkey};
+use yellowstone_grpc_proto::{geyser::SubscribeUpdateTransactionInfo, prelude::{InnerInstruction, InnerInstructions, TransactionStatusMeta}};
+
+use config::DexConfig;
+
+pub mod config;
+mod error;
+#[cfg(test)]
+pub(crate) mod fixtures;
+mod meteora;
+mod pump;
+mod raydium;
+mod whirlpool;
+
+pub use error::DecodeError;
+pub use meteora::{meteora_decoder, meteora_dlmm_decoder, DLMM_PUBKEY, METEORA_PUBKEY};
+pub use pump::{pump_amm_decoder, pump_fun_decoder, PDF2_PUBKEY, PDF_PUBKEY};
+pub use raydium::{raydium_cpmm_decoder, raydium_launchpad_decoder, raydium_v4_decoder, RAYDIUM_LP_PUBKEY, RAYDIUM_V4_PUBKEY, RAYDIUM_V5_PUBKEY};
+pub use whirlpool::{whirlpool_decoder, WHIRLPOOL_PUBKEY};
+
+pub const WSOL_PUBKEY: Pubkey = Pubkey::from_str_const("So11111111111111111111111111111111111111112");
+/// Token-2022's wrapped sol
+pub const WSOL_2022_PUBKEY: Pubkey = Pubkey::from_str_const("9pan9bMn5HatX4EJdBwg9VgCa7Uz5HL8N1m5D3NdXejP");
+pub const JITO_TIP_ACCOUNTS: [Pubkey; 8] = [
+    Pubkey::from_str_const("96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5"),
+    Pubkey::from_str_const("HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe"),
+    Pubkey::from_str_const("Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY"),
+    Pubkey::from_str_const("ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49"),
+    Pubkey::from_str_const("DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh"),
+    Pubkey::from_str_const("ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt"),
+    Pubkey::from_str_const("DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL"),
+    Pubkey::from_str_const("3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT"),
+];
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Swap {
+    pub outer_program: Option<String>,
+    pub program: String,
+    pub amm: String,
+    pub signer: String,
+    pub subject: String,
+    pub input_mint: String,
+    pub output_mint: String,
+    pub input_amount: u64,
+    pub output_amount: u64,
+    pub slot: u64,
+    pub order: u64,
+    pub sig: String,
+    pub input_decimals: Option<u8>,
+    pub output_decimals: Option<u8>,
+    pub input_ui_amount: Option<f64>,
+    pub output_ui_amount: Option<f64>,
+    pub input_symbol: Option<String>,
+    pub output_symbol: Option<String>,
+}
+
+impl Swap {
+    /// Sets the decimals of the mints along with the ui amounts derived from them
+    pub fn set_decimals(&mut self, input_decimals: Option<u8>, output_decimals: Option<u8>) {
+        self.input_decimals = input_decimals;
+        self.output_decimals = output_decimals;
+        self.input_ui_amount = input_decimals.map(|decimals| self.input_amount as f64 / 10f64.powi(decimals as i32));
+        self.output_ui_amount = output_decimals.map(|decimals| self.output_amount as f64 / 10f64.powi(decimals as i32));
+    }
+
+    /// Position of the swap across blocks, i.e. (slot, order in block)
+    pub fn position(&self) -> (u64, u64) {
+        (self.slot, self.order)
+    }
+}
+
+impl Debug for Swap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("{\n")?;
+        f.write_str(&format!("  outer_program: \"{:?}\",\n", self.outer_program))?;
+        f.write_str(&format!("  program: \"{:?}\",\n", self.program))?;
+        f.write_str(&format!("  amm: \"{:?}\",\n", self.amm))?;
+        f.write_str(&format!("  signer: \"{:?}\",\n", self.signer))?;
+        f.write_str(&format!("  subject: \"{:?}\",\n", self.subject))?;
+        f.write_str(&format!("  input_mint: \"{:?}\",\n", self.input_mint))?;
+        f.write_str(&format!("  output_mint: \"{:?}\",\n", self.output_mint))?;
+        f.write_str(&format!("  input_amount: {},\n", self.input_amount))?;
+        f.write_str(&format!("  output_amount: {},\n", self.output_amount))?;
+        f.write_str(&format!("  slot: {},\n", self.slot))?;
+        f.write_str(&format!("  order: {},\n", self.order))?;
+        f.write_str(&format!("  sig: \"{}\",\n", self.sig))?;
+        f.write_str(&format!("  input_decimals: {:?},\n", self.input_decimals))?;
+        f.write_str(&format!("  output_decimals: {:?},\n", self.output_decimals))?;
+        f.write_str("}")?;
+        Ok(())
+    }
+}
+
+pub struct DecompiledTransaction {
+    pub sig: String,
+    pub slot: u64,
+    pub instructions: Vec<Instruction>,
+    pub swaps: Vec<Swap>,
+    pub payer: Pubkey,
+    pub order: u64,
+    /// lamports, base + priority fee
+    pub fee: u64,
+    /// system transfers, both top level and cpi, as (from, to, lamports)
+    pub transfers: Vec<(Pubkey, Pubkey, u64)>,
+    /// lamports sent to the jito tip accounts
+    pub tip: u64,
+}
+
+/// Parses a system transfer given the program, its first two accounts and the instruction data
+fn parse_sol_transfer(program_id: &Pubkey, from: Option<&Pubkey>, to: Option<&Pubkey>, data: &[u8]) -> Option<(Pubkey, Pubkey, u64)> {
+    // transfer is instruction 2 of the system program, followed by the lamports
+    if *program_id != solana_sdk::system_program::ID || data.len() != 12 || data[0..4] != [2, 0, 0, 0] {
+        return None;
+    }
+    Some((*from?, *to?, u64::from_le_bytes(data[4..12].try_into().ok()?)))
+}
+
+/// System transfers of the transaction, in execution order
+pub fn sol_transfers(ixs: &[Instruction], inner_ix_map: &HashMap<usize, &InnerInstructions>, account_keys: &[Pubkey]) -> Vec<(Pubkey, Pubkey, u64)> {
+    ixs.iter().enumerate().flat_map(|(i, ix)| {
+        let account = |j: usize| ix.accounts.get(j).map(|meta| &meta.pubkey);
+        let outer = parse_sol_transfer(&ix.program_id, account(0), account(1), &ix.data);
+        let inner = inner_ix_map.get(&i).into_iter().flat_map(|inner_ix| inner_ix.instructions.iter()).filter_map(|inner| {
+            let program_id = account_keys.get(inner.program_id_index as usize)?;
+            let account = |j: usize| inner.accounts.get(j).and_then(|index| account_keys.get(*index as usize));
+            parse_sol_transfer(program_id, account(0), account(1), &inner.data)
+        });
+        outer.into_iter().chain(inner).collect::<Vec<_>>()
+    }).collect()
+}
+
+/// Lamports sent to the jito tip accounts by the transfers
+pub fn tip_amount(transfers: &[(Pubkey, Pubkey, u64)]) -> u64 {
+    transfers.iter().filter(|(_, to, _)| JITO_TIP_ACCOUNTS.contains(to)).map(|(_, _, lamports)| lamports).sum()
+}
+
+pub fn pubkey_from_slice(slice: &[u8]) -> Pubkey {
+    Pubkey::new_from_array(slice.try_into().expect("slice with incorrect length"))
+}
+
+/// Like `pubkey_from_slice`, for keys that aren't guaranteed to be well formed
+pub fn try_pubkey_from_slice(slice: &[u8]) -> Result<Pubkey, DecodeError> {
+    let bytes: [u8; 32] = slice.get(0..32).and_then(|slice| slice.try_into().ok()).ok_or_else(|| DecodeError::InvalidPubkey(bs58::encode(slice).into_string()))?;
+    Ok(Pubkey::new_from_array(bytes))
+}
+
+pub fn resolve_lut_lookups(lut_cache: &DashMap<Pubkey, AddressLookupTableAccount>, msg: &yellowstone_grpc_proto::prelude::Message) -> Result<(Vec<Pubkey>, Vec<Pubkey>), DecodeError> {
+    let mut writable: Vec<Pubkey> = Vec::new();
+    let mut readonly: Vec<Pubkey> = Vec::new();
+    for table_lookup in msg.address_table_lookups.iter() {
+        let lut_key = try_pubkey_from_slice(&table_lookup.account_key)?;
+        // find the correct lut account
+        let lut = lut_cache.get(&lut_key).ok_or(DecodeError::LutNotFound(lut_key))?;
+        let address = |index: &u8| lut.addresses.get(*index as usize).copied().ok_or(DecodeError::LutIndexOutOfRange { lut: lut_key, index: *index });
+        for index in table_lookup.writable_indexes.iter() {
+            writable.push(address(index)?);
+        }
+        for index in table_lookup.readonly_indexes.iter() {
+            readonly.push(address(index)?);
+        }
+    }
+
+    Ok((writable, readonly))
+}
+
+/// Maps the different representations of sol to wsol, so that native and wrapped sol legs are treated as the same mint
+pub fn normalize_mint(mint: Pubkey) -> Pubkey {
+    if mint == WSOL_2022_PUBKEY || mint == solana_sdk::system_program::ID {
+        WSOL_PUBKEY
+    } else {
+        mint
+    }
+}
+
+/// Decimals of the mint as reported in the token balances of the transaction
+pub fn mint_decimals(meta: &TransactionStatusMeta, mint: &Pubkey) -> Option<u8> {
+    if *mint == WSOL_PUBKEY {
+        return Some(9);
+    }
+    let mint = mint.to_string();
+    meta.pre_token_balances.iter().chain(meta.post_token_balances.iter()).find(|x| x.mint == mint).and_then(|x| x.ui_token_amount.as_ref()).map(|x| x.decimals as u8)
+}
+
+/// The mint, subject account index and amount of a token transfer, `None` if the instruction isn't a transfer
+pub fn find_transferred_token(ix: &InnerInstruction, meta: &TransactionStatusMeta) -> Result<Option<(Pubkey, u8, u64)>, DecodeError> {
+    let account = |i: usize| ix.accounts.get(i).copied().ok_or(DecodeError::MalformedTransfer);
+    // transfer: 1/0; transferChecked: 2/0
+    let (i1, i0, subject_idx, range) = match ix.data.first() {
+        Some(2) => (99, 99, account(0)?, 4..12), // system program transfer
+        Some(3) => (account(1)?, account(0)?, account(2)?, 1..9), // transfer
+        Some(12) => (account(2)?, account(0)?, account(3)?, 1..9), // transferChecked
+        Some(228) => (99, 99, account(0)?, 48..56), // anchor self cpi log for pdf (no subject)
+        _ => return Ok(None),
+    };
+    let amount = u64::from_le_bytes(ix.data.get(range).and_then(|data| data.try_into().ok()).ok_or(DecodeError::MalformedTransfer)?);
+    if (i1, i0) == (99, 99) {
+        return Ok(Some((WSOL_PUBKEY, subject_idx, amount)));
+    }
+    // accounts closed within the tx only show up in the pre balances
+    let Some(balance) = meta.post_token_balances.iter().chain(meta.pre_token_balances.iter()).find(|x| x.account_index == i1 as u32 || x.account_index == i0 as u32) else {
+        return Ok(None);
+    };
+    let mint = Pubkey::from_str(&balance.mint).map_err(|_| DecodeError::InvalidPubkey(balance.mint.clone()))?;
+    Ok(Some((normalize_mint(mint), subject_idx, amount)))
+}
+
+/// Location of a swap within an instruction and its inner instructions.
+/// `send_ix_index`/`recv_ix_index` are offsets of the token transfers relative to the swap instruction.
+#[derive(Debug, Clone)]
+pub struct SwapLayout {
+    pub discriminant: Vec<u8>,
+    pub amm_index: usize,
+    pub send_ix_index: usize,
+    pub recv_ix_index: usize,
+    pub data_len: usize,
+}
+
+impl SwapLayout {
+    pub fn new(discriminant: &[u8], amm_index: usize, send_ix_index: usize, recv_ix_index: usize, data_len: usize) -> Self {
+        Self {
+            discriminant: discriminant.to_vec(),
+            amm_index,
+            send_ix_index,
+            recv_ix_index,
+            data_len,
+        }
+    }
+}
+
+pub struct DecodeContext<'a> {
+    pub meta: &'a TransactionStatusMeta,
+    pub account_keys: &'a Vec<Pubkey>,
+    pub sig: &'a str,
+    pub slot: u64,
+    pub tx_index: u64,
+}
+
+pub fn find_swaps(ix: &Instruction, inner_ix: &InnerInstructions, swap_program: &Pubkey, layout: &SwapLayout, ctx: &DecodeContext) -> Result<Vec<Swap>, DecodeError> {
+    let (meta, account_keys) = (ctx.meta, ctx.account_keys);
+    let discriminant = layout.discriminant.as_slice();
+    let key = |index: usize| account_keys.get(index).ok_or(DecodeError::AccountIndexOutOfRange(index));
+    let inner_at = |index: Option<usize>| {
+        index.and_then(|index| inner_ix.instructions.get(index)).ok_or(DecodeError::InnerInstructionOutOfRange { program: *swap_program, index: index.unwrap_or_default() })
+    };
+    let signer = key(0)?.to_string();
+    let mut swaps: Vec<Swap> = Vec::new();
+    // case 1
+    if ix.program_id == *swap_program && ix.data.len() == layout.data_len && ix.data.starts_with(discriminant) {
+        let send_inner_ix = inner_at(layout.send_ix_index.checked_sub(1))?;
+        let recv_inner_ix = inner_at(layout.recv_ix_index.checked_sub(1))?;
+        let input = find_transferred_token(send_inner_ix, meta)?;
+        let output = find_transferred_token(recv_inner_ix, meta)?;
+        if let Some(input) = input {
+            if let Some(output) = output {
+                let mut swap = Swap {
+                    outer_program: None,
+                    program: ix.program_id.to_string(),
+                    amm: ix.accounts.get(layout.amm_index).ok_or(DecodeError::AccountIndexOutOfRange(layout.amm_index))?.pubkey.to_string(),
+                    signer: signer.clone(),
+                    subject: key(input.1 as usize)?.to_string(),
+                    input_mint: input.0.to_string(),
+                    output_mint: output.0.to_string(),
+                    input_amount: input.2,
+                    output_amount: output.2,
+                    sig: ctx.sig.to_string(),
+                    slot: ctx.slot,
+                    order: ctx.tx_index,
+                    input_decimals: None,
+                    output_decimals: None,
+                    input_ui_amount: None,
+                    output_ui_amount: None,
+                    input_symbol: None,
+                    output_symbol: None,
+                };
+                swap.set_decimals(mint_decimals(meta, &input.0), mint_decimals(meta, &output.0));
+                swaps.push(swap);
+            }
+        }
+    }
+    // loop thru the inner ixs to find a swap
+    for (j, inner) in inner_ix.instructions.iter().enumerate() {
+        let program_id = *key(inner.program_id_index as usize)?;
+        if program_id != *swap_program || inner.data.len() != layout.data_len || !inner.data.starts_with(discriminant) {
+            continue; // not a swap
+        }
+        let send_inner_ix = inner_at(Some(j + layout.send_ix_index))?;
+        let recv_inner_ix = inner_at(Some(j + layout.recv_ix_index))?;
+        let input = find_transferred_token(send_inner_ix, meta)?;
+        let output = find_transferred_token(recv_inner_ix, meta)?;
+        if let Some(input) = input {
+            if let Some(output) = output {
+                let amm_index = *inner.accounts.get(layout.amm_index).ok_or(DecodeError::AccountIndexOutOfRange(layout.amm_index))? as usize;
+                let mut swap = Swap {
+                    outer_program: Some(ix.program_id.to_string()),
+                    program: program_id.to_string(),
+                    amm: key(amm_index)?.to_string(),
+                    signer: signer.clone(),
+                    subject: key(input.1 as usize)?.to_string(),
+                    input_mint: input.0.to_string(),
+                    output_mint: output.0.to_string(),
+                    input_amount: input.2,
+                    output_amount: output.2,
+                    sig: ctx.sig.to_string(),
+                    slot: ctx.slot,
+                    order: ctx.tx_index,
+                    input_decimals: None,
+                    output_decimals: None,
+                    input_ui_amount: None,
+                    output_ui_amount: None,
+                    input_symbol: None,
+                    output_symbol: None,
+                };
+                swap.set_decimals(mint_decimals(meta, &input.0), mint_decimals(meta, &output.0));
+                swaps.push(swap);
+            }
+        }
+    }
+    Ok(swaps)
+}
+
+/// A decoder for the swaps of a single on-chain program.
+/// Most DEXes only need to describe where their swaps are through `layouts`, the default `decode`
+/// handles both direct calls and CPIs. Decoders with unusual layouts can override `decode` instead.
+pub trait SwapDecoder: Send + Sync {
+    fn name(&self) -> &str;
+    fn program_id(&self) -> Pubkey;
+    fn layouts(&self) -> &[SwapLayout];
+
+    fn decode(&self, ix: &Instruction, inner_ix: &InnerInstructions, ctx: &DecodeContext) -> Result<Vec<Swap>, DecodeError> {
+        let program_id = self.program_id();
+        let mut swaps = Vec::new();
+        for layout in self.layouts() {
+            swaps.extend(find_swaps(ix, inner_ix, &program_id, layout, ctx)?);
+        }
+        Ok(swaps)
+    }
+}
+
+/// A decoder defined entirely by its program and layouts, used for the built-in DEXes and the ones of a `DexConfig`
+pub struct LayoutDecoder {
+    name: String,
+    program_id: Pubkey,
+    layouts: Vec<SwapLayout>,
+}
+
+impl LayoutDecoder {
+    pub fn new(name: &str, program_id: Pubkey, layouts: Vec<SwapLayout>) -> Self {
+        Self {
+            name: name.to_string(),
+            program_id,
+            layouts,
+        }
+    }
+}
+
+impl SwapDecoder for LayoutDecoder {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn program_id(&self) -> Pubkey {
+        self.program_id
+    }
+
+    fn layouts(&self) -> &[SwapLayout] {
+        &self.layouts
+    }
+}
+
+/// The set of decoders `decompile` runs against each instruction, in registration order.
+pub struct DecoderRegistry {
+    decoders: Vec<Box<dyn SwapDecoder>>,
+}
+
+impl DecoderRegistry {
+    pub fn new() -> Self {
+        Self {
+            decoders: Vec::new(),
+        }
+    }
+
+    pub fn register(&mut self, decoder: Box<dyn SwapDecoder>) {
+        self.decoders.push(decoder);
+    }
+
+    /// Registers the decoder, replacing any existing decoder of the same name.
+    /// A decoder for a program already decoded under another name is rejected, as every swap would be decoded twice.
+    pub fn merge(&mut self, decoder: Box<dyn SwapDecoder>) -> Result<(), String> {
+        if let Some(existing) = self.decoders.iter().find(|x| x.program_id() == decoder.program_id() && x.name() != decoder.name()) {
+            return Err(format!("{}: program {} is already decoded by {}", decoder.name(), decoder.program_id(), existing.name()));
+        }
+        match self.decoders.iter().position(|x| x.name() == decoder.name()) {
+            Some(i) => self.decoders[i] = decoder,
+            None => self.decoders.push(decoder),
+        }
+        Ok(())
+    }
+
+    /// Merges the validated definitions of the config
+    pub fn merge_config(&mut self, config: &DexConfig) -> Result<(), String> {
+        for decoder in config.validate()? {
+            println!("loaded dex definition: {} ({})", decoder.name(), decoder.program_id());
+            self.merge(Box::new(decoder))?;
+        }
+        Ok(())
+    }
+
+    /// The built-in decoders merged with the definitions in the file at `path`
+    pub fn with_config(path: &Path) -> Result<Self, String> {
+        let mut registry = Self::default();
+        registry.merge_config(&DexConfig::load(path)?)?;
+        Ok(registry)
+    }
+
+    pub fn decoders(&self) -> &[Box<dyn SwapDecoder>] {
+        &self.decoders
+    }
+
+    pub fn decode(&self, ix: &Instruction, inner_ix: &InnerInstructions, ctx: &DecodeContext) -> Result<Vec<Swap>, DecodeError> {
+        let mut swaps = Vec::new();
+        for decoder in self.decoders.iter() {
+            swaps.extend(decoder.decode(ix, inner_ix, ctx)?);
+        }
+        Ok(swaps)
+    }
+}
+
+impl Default for DecoderRegistry {
+    /// All the built-in decoders
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register(Box::new(raydium_v4_decoder()));
+        registry.register(Box::new(raydium_cpmm_decoder()));
+        registry.register(Box::new(raydium_launchpad_decoder()));
+        registry.register(Box::new(pump_fun_decoder()));
+        registry.register(Box::new(pump_amm_decoder()));
+        registry.register(Box::new(whirlpool_decoder()));
+        registry.register(Box::new(meteora_dlmm_decoder()));
+        registry.register(Box::new(meteora_decoder()));
+        registry
+    }
+}
+
+/// Addresses loaded through the lookup tables of the tx, as (writable, readonly).
+/// The addresses reported in the tx meta are preferred as luts may have changed since, otherwise they're
+/// resolved through the lut cache, fetching uncached luts if an rpc client is given.
+async fn load_lookup_addresses(msg: &yellowstone_grpc_proto::prelude::Message, meta: &TransactionStatusMeta, rpc_client: Option<&RpcClient>, lut_cache: &DashMap<Pubkey, AddressLookupTableAccount>) -> Result<(Vec<Pubkey>, Vec<Pubkey>), DecodeError> {
+    let lookup_count = msg.address_table_lookups.iter().map(|lut| lut.writable_indexes.len() + lut.readonly_indexes.len()).sum::<usize>();
+    if lookup_count == 0 {
+        return Ok((Vec::new(), Vec::new()));
+    }
+    if meta.loaded_writable_addresses.len() + meta.loaded_readonly_addresses.len() == lookup_count {
+        let writable = meta.loaded_writable_addresses.iter().map(|key| try_pubkey_from_slice(key)).collect::<Result<_, _>>()?;
+        let readonly = meta.loaded_readonly_addresses.iter().map(|key| try_pubkey_from_slice(key)).collect::<Result<_, _>>()?;
+        return Ok((writable, readonly));
+    }
+    let lut_keys = msg.address_table_lookups.iter().map(|lut| {
+        try_pubkey_from_slice(&lut.account_key)
+    }).collect::<Result<Vec<Pubkey>, _>>()?;
+
+    // get the uncached lut accounts, deserialize them and cache them
+    let uncached_luts = lut_keys.iter().filter(|lut_key| !lut_cache.contains_key(lut_key)).map(|x| *x).collect::<Vec<Pubkey>>();
+    // offline callers are expected to have populated the cache beforehand
+    if let Some(rpc_client) = rpc_client.filter(|_| !uncached_luts.is_empty()) {
+        let accounts = rpc_client.get_multiple_accounts(uncached_luts.as_slice()).await.map_err(|e| DecodeError::Rpc(e.to_string()))?;
+        for (key, account) in uncached_luts.iter().zip(accounts) {
+            if let Some(account) = account {
+                let lut = AddressLookupTable::deserialize(&account.data()).map_err(|_| DecodeError::InvalidLut(*key))?;
+                lut_cache.insert(*key, AddressLookupTableAccount {
+                    key: *key,
+                    addresses: lut.addresses.to_vec(),
+                });
+            }
+        }
+    }
+
+    // can't resolve lookups without the lut (offline, or the lut is gone)
+    resolve_lut_lookups(lut_cache, msg)
+}
+
+/// Decodes the swaps and sol transfers of the transaction.
+/// Failed transactions are `Ok(None)`, malformed ones or those with luts that can't be resolved are errors.
+pub async fn decompile(raw_tx: &SubscribeUpdateTransactionInfo, slot: u64, rpc_client: Option<&RpcClient>, lut_cache: &DashMap<Pubkey, AddressLookupTableAccount>, decoders: &DecoderRegistry) -> Result<Option<DecompiledTransaction>, DecodeError> {
+    if let Some(tx) = &raw_tx.transaction {
+        if let Some(meta) = &raw_tx.meta {
+            // no swaps in failed txs
+            if meta.err.is_some() {
+                return Ok(None);
+            }
+            if let Some(msg) = &tx.message {
+                if let Some(header) = &msg.header {
+                    let sig = bs58::encode(&raw_tx.signature).into_string();
+                    // resolve lookups
+                    let (writable, readonly) = load_lookup_addresses(msg, meta, rpc_client, lut_cache).await?;
+                    let num_signed_accts = header.num_required_signatures as usize;
+                    let num_static_keys = msg.account_keys.len();
+                    let num_writable_lut_keys = writable.len();
+
+                    let mut account_keys: Vec<Pubkey> = msg.account_keys.iter().map(|key| try_pubkey_from_slice(key)).collect::<Result<_, _>>()?;
+                    account_keys.extend(writable);
+                    account_keys.extend(readonly);
+                    let key = |index: usize| account_keys.get(index).copied().ok_or(DecodeError::AccountIndexOutOfRange(index));
+
+                    // repackage into legacy ixs
+                    let ixs = msg.instructions.iter().map(|ix| {
+                        let program_id = key(ix.program_id_index as usize)?;
+                        let accounts = ix.accounts.iter().enumerate().map(|(i, index)| {
+                            let is_signer = i < num_signed_accts;
+                            let is_writable = if i >= num_static_keys {
+                                i - num_static_keys < num_writable_lut_keys
+                            } else if i >= num_signed_accts {
+                                i - num_signed_accts < num_static_keys.saturating_sub(num_signed_accts + header.num_readonly_unsigned_accounts as usize)
+                            } else {
+                                i < num_signed_accts.saturating_sub(header.num_readonly_signed_accounts as usize)
+                            };
+                            Ok(AccountMeta {
+                                pubkey: key(*index as usize)?,
+                                is_signer,
+                                is_writable,
+                            })
+                        }).collect::<Result<Vec<AccountMeta>, DecodeError>>()?;
+                        Ok(Instruction {
+                            program_id,
+                            accounts,
+                            data: ix.data.clone(),
+                        })
+                    }).collect::<Result<Vec<Instruction>, DecodeError>>()?;
+
+                    // find swaps from the ixs
+                    // swaps can occur in 2 forms:
+                    // 1. as a direct call to the swap program, in that case we should see the send/receive as inner ixs
+                    // 2. as a cpi, in that case we should see the swap program call followed by the transfers
+                    let mut inner_ix_map: HashMap<usize, &InnerInstructions> = HashMap::new();
+                    meta.inner_instructions.iter().for_each(|inner_ix| {
+                        inner_ix_map.insert(inner_ix.index as usize, inner_ix);
+                    });
+                    let ctx = DecodeContext {
+                        meta,
+                        account_keys: &account_keys,
+                        sig: &sig,
+                        slot,
+                        tx_index: raw_tx.index,
+                    };
+                    let mut swaps: Vec<Swap> = Vec::new();
+                    for (i, ix) in ixs.iter().enumerate() {
+                        if let Some(inner_ix) = inner_ix_map.get(&i) {
+                            swaps.extend(decoders.decode(ix, inner_ix, &ctx)?);
+                        }
+                    }
+                    let transfers = sol_transfers(&ixs, &inner_ix_map, &account_keys);
+                    return Ok(Some(DecompiledTransaction {
+                        sig,
+                        slot,
+                        payer: key(0)?,
+                        instructions: ixs,
+                        swaps,
+                        order: raw_tx.index,
+                        fee: meta.fee,
+                        tip: tip_amount(&transfers),
+                        transfers,
+                    }));
+                }
+            }
+        }
+    }
+    Ok(None)
+}
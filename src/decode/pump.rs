@@ -0,0 +1,54 @@
+use solana_sdk::pubkey::Pubkey;
+
+use super::{LayoutDecoder, SwapLayout};
+
+pub const PDF_PUBKEY: Pubkey = Pubkey::from_str_const("6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P");
+pub const PDF2_PUBKEY: Pubkey = Pubkey::from_str_const("pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA");
+
+const BUY_DISCRIMINANT: [u8; 8] = [0x66, 0x06, 0x3d, 0x12, 0x01, 0xda, 0xeb, 0xea];
+const SELL_DISCRIMINANT: [u8; 8] = [0x33, 0xe6, 0x85, 0xa4, 0x01, 0x7f, 0x83, 0xad];
+
+/// Pump.fun bonding curve
+pub fn pump_fun_decoder() -> LayoutDecoder {
+    LayoutDecoder::new("pump_fun", PDF_PUBKEY, vec![
+        // buy sends sol after receiving the tokens
+        SwapLayout::new(&BUY_DISCRIMINANT, 3, 2, 1, 24),
+        SwapLayout::new(&SELL_DISCRIMINANT, 3, 1, 2, 24),
+    ])
+}
+
+/// Pump.fun AMM
+pub fn pump_amm_decoder() -> LayoutDecoder {
+    LayoutDecoder::new("pump_amm", PDF2_PUBKEY, vec![
+        SwapLayout::new(&BUY_DISCRIMINANT, 0, 2, 1, 24),
+        SwapLayout::new(&SELL_DISCRIMINANT, 0, 1, 2, 24),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::{fixtures::*, WSOL_PUBKEY};
+
+    #[test]
+    fn decodes_pump_fun_buy() {
+        // the tokens are received before the sol is sent
+        let swaps = decode(&pump_fun_decoder(), PROGRAM, &[TOKEN_PROGRAM, TOKEN_PROGRAM, TOKEN_PROGRAM, AMM], ix_data(&BUY_DISCRIMINANT, 24), vec![
+            transfer(POOL_B, USER_B, AMM, 5000),
+            sol_transfer(SIGNER, AMM, 100),
+        ]);
+        assert_eq!(swaps.len(), 1);
+        assert_swap(&swaps[0], PDF_PUBKEY, (WSOL_PUBKEY, 100), (MINT_B, 5000));
+        assert_eq!(swaps[0].input_decimals, Some(9));
+    }
+
+    #[test]
+    fn decodes_pump_amm_sell() {
+        let swaps = decode(&pump_amm_decoder(), PROGRAM, &[AMM, SIGNER], ix_data(&SELL_DISCRIMINANT, 24), vec![
+            transfer(USER_B, POOL_B, SIGNER, 5000),
+            transfer(POOL_A, USER_A, AMM, 100),
+        ]);
+        assert_eq!(swaps.len(), 1);
+        assert_swap(&swaps[0], PDF2_PUBKEY, (MINT_B, 5000), (MINT_A, 100));
+    }
+}
@@ -0,0 +1,95 @@
+use solana_sdk::pubkey::Pubkey;
+
+use super::{LayoutDecoder, SwapLayout};
+
+pub const RAYDIUM_V4_PUBKEY: Pubkey = Pubkey::from_str_const("675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8");
+pub const RAYDIUM_V5_PUBKEY: Pubkey = Pubkey::from_str_const("CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C");
+pub const RAYDIUM_LP_PUBKEY: Pubkey = Pubkey::from_str_const("LanMV9sAd7wArD4vJFi2qDdfnVhFxYSUg6eADduJ3uj");
+
+/// Raydium AMM v4
+pub fn raydium_v4_decoder() -> LayoutDecoder {
+    LayoutDecoder::new("raydium_v4", RAYDIUM_V4_PUBKEY, vec![
+        // swap: 09/amountIn u64/minOut u64, and the 2nd account is the amm id
+        SwapLayout::new(&[0x09], 1, 1, 2, 17),
+    ])
+}
+
+/// Raydium CPMM (v5)
+pub fn raydium_cpmm_decoder() -> LayoutDecoder {
+    LayoutDecoder::new("raydium_cpmm", RAYDIUM_V5_PUBKEY, vec![
+        // swap_base_input/swap_base_output
+        SwapLayout::new(&[0x8f, 0xbe, 0x5a, 0xda, 0xc4, 0x1e, 0x33, 0xde], 3, 1, 2, 24),
+        SwapLayout::new(&[0x37, 0xd9, 0x62, 0x56, 0xa3, 0x4a, 0xb4, 0xad], 3, 1, 2, 24),
+    ])
+}
+
+/// Raydium LaunchLab bonding curves
+pub fn raydium_launchpad_decoder() -> LayoutDecoder {
+    LayoutDecoder::new("raydium_launchpad", RAYDIUM_LP_PUBKEY, vec![
+        // buy_exact_in/sell_exact_in
+        SwapLayout::new(&[0xfa, 0xea, 0x0d, 0x7b, 0xd5, 0x9c, 0x13, 0xec], 4, 2, 3, 32),
+        SwapLayout::new(&[0x95, 0x27, 0xde, 0x9b, 0xd3, 0x7c, 0x98, 0x1a], 4, 2, 3, 32),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::fixtures::*;
+
+    #[test]
+    fn decodes_v4_swap() {
+        let swaps = decode(&raydium_v4_decoder(), PROGRAM, &[TOKEN_PROGRAM, AMM, SIGNER], ix_data(&[0x09], 17), vec![
+            transfer(USER_A, POOL_A, SIGNER, 100),
+            transfer(POOL_B, USER_B, AMM, 250),
+        ]);
+        assert_eq!(swaps.len(), 1);
+        assert_swap(&swaps[0], RAYDIUM_V4_PUBKEY, (MINT_A, 100), (MINT_B, 250));
+        assert_eq!(swaps[0].outer_program, None);
+        assert_eq!((swaps[0].input_decimals, swaps[0].output_decimals), (Some(6), Some(9)));
+    }
+
+    #[test]
+    fn decodes_cpmm_swap_through_cpi() {
+        let decoder = raydium_cpmm_decoder();
+        let data = ix_data(&[0x8f, 0xbe, 0x5a, 0xda, 0xc4, 0x1e, 0x33, 0xde], 24);
+        let swaps = decode(&decoder, AGGREGATOR, &[SIGNER], vec![0x01], vec![
+            program_call(vec![SIGNER, TOKEN_PROGRAM, TOKEN_PROGRAM, AMM], data),
+            transfer(USER_B, POOL_B, SIGNER, 250),
+            transfer(POOL_A, USER_A, AMM, 100),
+        ]);
+        assert_eq!(swaps.len(), 1);
+        assert_swap(&swaps[0], RAYDIUM_V5_PUBKEY, (MINT_B, 250), (MINT_A, 100));
+        assert_eq!(swaps[0].outer_program, Some(key(RAYDIUM_V5_PUBKEY, AGGREGATOR)));
+    }
+
+    #[test]
+    fn decodes_launchpad_buy() {
+        let data = ix_data(&[0xfa, 0xea, 0x0d, 0x7b, 0xd5, 0x9c, 0x13, 0xec], 32);
+        let swaps = decode(&raydium_launchpad_decoder(), PROGRAM, &[SIGNER, SIGNER, TOKEN_PROGRAM, TOKEN_PROGRAM, AMM], data, vec![
+            other(),
+            transfer(USER_A, POOL_A, SIGNER, 100),
+            transfer(POOL_B, USER_B, AMM, 250),
+        ]);
+        assert_eq!(swaps.len(), 1);
+        assert_swap(&swaps[0], RAYDIUM_LP_PUBKEY, (MINT_A, 100), (MINT_B, 250));
+    }
+
+    #[test]
+    fn ignores_cpmm_swap_under_launchpad() {
+        let swaps = decode(&raydium_launchpad_decoder(), PROGRAM, &[SIGNER, TOKEN_PROGRAM, TOKEN_PROGRAM, AMM], ix_data(&[0x8f, 0xbe, 0x5a, 0xda, 0xc4, 0x1e, 0x33, 0xde], 24), vec![
+            transfer(USER_A, POOL_A, SIGNER, 100),
+            transfer(POOL_B, USER_B, AMM, 250),
+        ]);
+        assert!(swaps.is_empty());
+    }
+
+    #[test]
+    fn ignores_other_instructions() {
+        let swaps = decode(&raydium_v4_decoder(), PROGRAM, &[TOKEN_PROGRAM, AMM], ix_data(&[0x0b], 17), vec![
+            transfer(USER_A, POOL_A, SIGNER, 100),
+            transfer(POOL_B, USER_B, AMM, 250),
+        ]);
+        assert!(swaps.is_empty());
+    }
+}
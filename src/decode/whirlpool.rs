@@ -0,0 +1,37 @@
+use solana_sdk::pubkey::Pubkey;
+
+use super::{LayoutDecoder, SwapLayout};
+
+pub const WHIRLPOOL_PUBKEY: Pubkey = Pubkey::from_str_const("whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc");
+
+/// Orca Whirlpool
+pub fn whirlpool_decoder() -> LayoutDecoder {
+    LayoutDecoder::new("whirlpool", WHIRLPOOL_PUBKEY, vec![
+        SwapLayout::new(&[0xf8, 0xc6, 0x9e, 0x91, 0xe1, 0x75, 0x87, 0xc8], 2, 1, 2, 42),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::fixtures::*;
+
+    #[test]
+    fn decodes_swap() {
+        let swaps = decode(&whirlpool_decoder(), PROGRAM, &[TOKEN_PROGRAM, SIGNER, AMM], ix_data(&[0xf8, 0xc6, 0x9e, 0x91, 0xe1, 0x75, 0x87, 0xc8], 42), vec![
+            transfer(USER_A, POOL_A, SIGNER, 100),
+            transfer(POOL_B, USER_B, AMM, 250),
+        ]);
+        assert_eq!(swaps.len(), 1);
+        assert_swap(&swaps[0], WHIRLPOOL_PUBKEY, (MINT_A, 100), (MINT_B, 250));
+    }
+
+    #[test]
+    fn ignores_wrong_data_len() {
+        let swaps = decode(&whirlpool_decoder(), PROGRAM, &[TOKEN_PROGRAM, SIGNER, AMM], ix_data(&[0xf8, 0xc6, 0x9e, 0x91, 0xe1, 0x75, 0x87, 0xc8], 24), vec![
+            transfer(USER_A, POOL_A, SIGNER, 100),
+            transfer(POOL_B, USER_B, AMM, 250),
+        ]);
+        assert!(swaps.is_empty());
+    }
+}
@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+
+use serde::{ser::SerializeStruct, Deserialize, Serialize};
+
+use crate::{amm::{model_for, VictimLoss}, confidence::{score_sandwiches, Signals}, decode::{DecompiledTransaction, Swap, WSOL_PUBKEY}, rules::RuleSet};
+
+/// The sandwicher's realised profit, from the frontrun and backrun legs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Profit {
+    /// mint paid in the frontrun and received in the backrun
+    pub quote_mint: String,
+    /// backrun output - frontrun input, in the quote mint
+    pub gross: i64,
+    /// frontrun output - backrun input, what's left of the other mint
+    pub leftover: i64,
+    /// lamports paid in tx fees (incl. priority fees) by both legs
+    pub fees: u64,
+    /// lamports paid in jito tips by both legs, see `Sandwich::frontrun_tip`
+    pub tips: u64,
+    /// gross profit net of fees and tips, only when the quote mint is wsol
+    pub net: Option<i64>,
+}
+
+impl Profit {
+    /// `None` if any of the amounts doesn't fit in an i64, which no real sandwich comes close to
+    pub fn new(frontrun: &Swap, backrun: &Swap, fees: u64, tips: u64) -> Option<Self> {
+        let gross = i64::try_from(backrun.output_amount as i128 - frontrun.input_amount as i128).ok()?;
+        let leftover = i64::try_from(frontrun.output_amount as i128 - backrun.input_amount as i128).ok()?;
+        let net = if frontrun.input_mint == WSOL_PUBKEY.to_string() {
+            Some(i64::try_from(gross as i128 - fees as i128 - tips as i128).ok()?)
+        } else {
+            None
+        };
+        Some(Self {
+            quote_mint: frontrun.input_mint.clone(),
+            gross,
+            leftover,
+            fees,
+            tips,
+            net,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Sandwich {
+    /// slot of the frontrun
+    pub slot: u64,
+    pub frontrun: Swap,
+    pub victim: Vec<Swap>,
+    pub backrun: Swap,
+    pub ts: i64,
+    pub profit: Profit,
+    /// total victim loss in sol, see `price::value_sandwiches`
+    pub loss_sol: Option<f64>,
+    /// sandwicher profit in sol net of fees and tips
+    pub profit_sol: Option<f64>,
+    /// name of the rule set that matched the sandwich
+    pub rule_set: String,
+    pub rule_version: u32,
+    /// how likely this is an actual sandwich within [0, 1], see `confidence::Signals`
+    pub confidence: f64,
+    pub signals: Option<Signals>,
+    /// entity behind the sandwich, see `cluster::SandwicherClusters`
+    pub sandwicher_id: Option<u64>,
+    /// wallets and programs that linked the sandwich to its sandwicher
+    pub sandwicher_members: Vec<String>,
+    /// ids of older entities merged into `sandwicher_id` by this sandwich, rewritten on the sandwiches already stored
+    pub merged_sandwicher_ids: Vec<u64>,
+    /// lamports tipped by the frontrun, including a separate tip tx of the same payer right before it
+    pub frontrun_tip: u64,
+    /// lamports tipped by the backrun, including a separate tip tx of the same payer right after it
+    pub backrun_tip: u64,
+    /// bundle of the frontrun, or of the backrun if the frontrun wasn't bundled, only known with a bundle source, see `bundle::BundleSource`
+    pub bundle_id: Option<String>,
+    /// whether both legs landed in the same bundle, `None` without a bundle source
+    pub bundled: Option<bool>,
+}
+
+impl Sandwich {
+    /// Fees, tips and the confidence are filled in later from the transactions of the legs, see `find_block_sandwiches`.
+    /// `None` if the profit overflows, see `Profit::new`
+    pub fn new(slot: u64, frontrun: Swap, victim: Vec<Swap>, backrun: Swap, ts: i64, rules: &RuleSet) -> Option<Self> {
+        Some(Self {
+            profit: Profit::new(&frontrun, &backrun, 0, 0)?,
+            slot,
+            frontrun,
+            victim,
+            backrun,
+            ts,
+            loss_sol: None,
+            profit_sol: None,
+            rule_set: rules.name.clone(),
+            rule_version: rules.version,
+            confidence: 0.0,
+            signals: None,
+            sandwicher_id: None,
+            sandwicher_members: Vec::new(),
+            merged_sandwicher_ids: Vec::new(),
+            frontrun_tip: 0,
+            backrun_tip: 0,
+            bundle_id: None,
+            bundled: None,
+        })
+    }
+
+    /// Whether the backrun went through a different pool of the pair than the frontrun
+    pub fn is_cross_amm(&self) -> bool {
+        self.frontrun.amm != self.backrun.amm
+    }
+
+    /// Whether the legs landed in different slots of the leader group
+    pub fn is_cross_slot(&self) -> bool {
+        self.frontrun.slot != self.backrun.slot
+    }
+
+    /// Estimates the loss of each victim using the pricing model of the sandwiched amm
+    pub fn estimate_victim_losses(&self) -> Result<Vec<VictimLoss>, String> {
+        model_for(&self.frontrun.program).victim_losses(&self.frontrun, &self.victim)
+    }
+}
+
+impl Serialize for Sandwich {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer {
+        let mut state = serializer.serialize_struct("Sandwich", 20)?;
+        state.serialize_field("slot", &self.slot)?;
+        state.serialize_field("frontrun", &self.frontrun)?;
+        state.serialize_field("victim", &self.victim)?;
+        state.serialize_field("backrun", &self.backrun)?;
+        state.serialize_field("ts", &self.ts)?;
+        state.serialize_field("profit", &self.profit)?;
+        state.serialize_field("loss_sol", &self.loss_sol)?;
+        state.serialize_field("profit_sol", &self.profit_sol)?;
+        state.serialize_field("cross_amm", &self.is_cross_amm())?;
+        state.serialize_field("cross_slot", &self.is_cross_slot())?;
+        state.serialize_field("confidence", &self.confidence)?;
+        state.serialize_field("signals", &self.signals)?;
+        state.serialize_field("sandwicher_id", &self.sandwicher_id)?;
+        state.serialize_field("frontrun_tip", &self.frontrun_tip)?;
+        state.serialize_field("backrun_tip", &self.backrun_tip)?;
+        state.serialize_field("bundle_id", &self.bundle_id)?;
+        state.serialize_field("bundled", &self.bundled)?;
+        state.serialize_field("rule_set", &self.rule_set)?;
+        state.serialize_field("rule_version", &self.rule_version)?;
+        state.end()
+    }
+}
+
+pub fn find_sandwiches(in_trades: &Vec<&Swap>, out_trades: &Vec<&Swap>, slot: u64, ts: i64, rules: &RuleSet) -> Vec<Sandwich> {
+    // for each in_trade, we look for an out_trade that satisfies the sandwich criteria
+    // since we've already went this far, we just need to pass check 1 and the configurable rules (3, 5, 6, 7)
+    // and we can consider all trades between the in/out trades to be sandwiched
+    let mut sandwiches = Vec::new();
+    for i in 0..in_trades.len() {
+        for j in (0..out_trades.len()).rev() {
+            let in_trade = in_trades[i];
+            let out_trade = out_trades[j];
+            // check #1
+            if out_trade.position() <= in_trade.position() {
+                // subsequent out_trade's will have even lower order
+                break;
+            }
+            if !rules.check_legs(in_trade, out_trade) {
+                continue;
+            }
+            // these two trades form the sandwich, now we just need to find the victims (in_trades between in_trade and out_trade)
+            let mut victims: Vec<Swap> = Vec::new();
+            for k in i+1..in_trades.len() {
+                let victim = in_trades[k];
+                // check #1
+                if victim.position() >= out_trade.position() {
+                    // subsequent in_trade's will have even higher order
+                    break;
+                }
+                // check #4
+                if victim.amm != in_trade.amm {
+                    continue;
+                }
+                if !rules.check_victim(in_trade, victim, out_trade) {
+                    continue;
+                }
+                victims.push(victim.clone());
+            }
+            if !victims.is_empty() && rules.check_victims(&victims) {
+                match Sandwich::new(slot, in_trade.clone(), victims, out_trade.clone(), ts, rules) {
+                    Some(sandwich) => sandwiches.push(sandwich),
+                    None => println!("skipping sandwich {}-{}: profit overflows", in_trade.sig, out_trade.sig),
+                }
+            }
+        }
+    }
+    sandwiches
+}
+
+/// The swap's mints in a direction independent order
+fn swap_pair(swap: &Swap) -> (&String, &String) {
+    if swap.input_mint < swap.output_mint {
+        (&swap.input_mint, &swap.output_mint)
+    } else {
+        (&swap.output_mint, &swap.input_mint)
+    }
+}
+
+/// Runs sandwich detection over the decompiled transactions of a single block, once per rule set.
+/// `block_txs` is expected to be sorted by inclusion order.
+pub fn find_block_sandwiches(block_txs: &[&DecompiledTransaction], slot: u64, ts: i64, rule_sets: &[RuleSet]) -> Vec<Sandwich> {
+    // criteria for sandwiches:
+    // 1. has 3 txs of strictly increasing inclusion order (frontrun-victim-backrun)
+    // 2. the 1st and 2nd are in the same direction, the 3rd is in reverse
+    // 3. output of 3rd tx >= input of 1st tx && output of 1st tx >= input of 3rd tx (profitability constraint)
+    // 4. all 3 txs trade the same pair, and the 1st and 2nd use the same amm
+    // 5. 2nd tx's swapper is different from the 1st and 3rd
+    // 6. a wrapper program is present in the 1st and 3rd txs and are the same
+    // 7. if the 3rd tx uses a different amm than the 1st (cross-amm), they have the same signer
+    // 3, 5, 6 and 7 are configurable, see `rules`
+
+    // a pool only trades a single pair, swaps decoded with any other mints are dropped instead of spoiling the whole pool
+    let mut amm_pairs: HashMap<&String, HashMap<(&String, &String), usize>> = HashMap::new();
+    block_txs.iter().flat_map(|tx| tx.swaps.iter()).filter(|swap| swap.input_mint != swap.output_mint).for_each(|swap| {
+        let count = amm_pairs.entry(&swap.amm).or_insert(HashMap::new()).entry(swap_pair(swap)).or_insert(0);
+        *count += 1;
+    });
+    let canonical_pairs = amm_pairs.into_iter().filter_map(|(amm, pairs)| {
+        // ties are broken by the pair itself to keep the result deterministic
+        let pair = pairs.into_iter().max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))?.0;
+        Some((amm, pair))
+    }).collect::<HashMap<_, _>>();
+
+    // group swaps by pair, the victim of a routed swap only has the hop through the sandwiched pool in here
+    let mut pair_swaps: HashMap<(&String, &String), Vec<&Swap>> = HashMap::new();
+    block_txs.iter().for_each(|tx| {
+        tx.swaps.iter().filter(|swap| canonical_pairs.get(&swap.amm) == Some(&swap_pair(swap))).for_each(|swap| {
+            let swaps = pair_swaps.entry(swap_pair(swap)).or_insert(Vec::new());
+            swaps.push(swap);
+        });
+    });
+
+    let mut sandwiches = Vec::new();
+    // check #4
+    pair_swaps.iter().for_each(|(_pair, swaps)| {
+        if swaps.len() < 3 {
+            return;
+        }
+        // within the group, further group by direction (input token)
+        let mut input_swaps: HashMap<&String, Vec<&Swap>> = HashMap::new();
+        swaps.iter().for_each(|swap| {
+            let input_swaps = input_swaps.entry(&swap.input_mint).or_insert(Vec::new());
+            input_swaps.push(swap);
+        });
+        // no sandwiches without trades in both directions
+        if input_swaps.len() != 2 {
+            return;
+        }
+        let mut iter = input_swaps.iter();
+        let dir0 = iter.next().unwrap();
+        let dir1 = iter.next().unwrap();
+        rule_sets.iter().for_each(|rules| {
+            // look for 0-0-1 sandwiches (check #2)
+            sandwiches.extend(find_sandwiches(dir0.1, dir1.1, slot, ts, rules));
+            // look for 1-1-0 sandwiches (check #2)
+            sandwiches.extend(find_sandwiches(dir1.1, dir0.1, slot, ts, rules));
+        });
+    });
+    // account for what the sandwicher paid to land the legs
+    let txs = block_txs.iter().map(|tx| (tx.sig.as_str(), *tx)).collect::<HashMap<_, _>>();
+    let positions = block_txs.iter().map(|tx| ((tx.slot, tx.order), *tx)).collect::<HashMap<_, _>>();
+    // bundles often tip in a separate tx of the same payer, right before the frontrun or after the backrun
+    let leg_tip = |leg: &Swap, neighbour: Option<u64>| {
+        let Some(leg_tx) = txs.get(leg.sig.as_str()) else {
+            return 0;
+        };
+        let tip_tx = neighbour.and_then(|order| positions.get(&(leg.slot, order))).filter(|tx| tx.swaps.is_empty() && tx.payer == leg_tx.payer);
+        leg_tx.tip + tip_tx.map_or(0, |tx| tx.tip)
+    };
+    sandwiches.retain_mut(|sandwich| {
+        let legs = [&sandwich.frontrun.sig, &sandwich.backrun.sig];
+        let fees = legs.iter().filter_map(|sig| txs.get(sig.as_str())).map(|tx| tx.fee).sum();
+        sandwich.frontrun_tip = leg_tip(&sandwich.frontrun, sandwich.frontrun.order.checked_sub(1));
+        sandwich.backrun_tip = leg_tip(&sandwich.backrun, Some(sandwich.backrun.order + 1));
+        let Some(profit) = Profit::new(&sandwich.frontrun, &sandwich.backrun, fees, sandwich.frontrun_tip.saturating_add(sandwich.backrun_tip)) else {
+            println!("skipping sandwich {}-{}: net profit overflows", sandwich.frontrun.sig, sandwich.backrun.sig);
+            return false;
+        };
+        sandwich.profit = profit;
+        true
+    });
+    score_sandwiches(&mut sandwiches, block_txs);
+    sandwiches
+}
+
+/// Number of consecutive slots each leader is scheduled for
+pub const LEADER_GROUP_SLOTS: u64 = 4;
+
+/// The transactions with swaps or tips of the blocks seen so far in the current leader group, so that sandwiches
+/// landing across its slots can be found. Blocks are expected to be pushed in slot order.
+#[derive(Default)]
+pub struct LeaderWindow {
+    group: Option<u64>,
+    /// (slot, ts) of the blocks in the window
+    blocks: Vec<(u64, i64)>,
+    txs: Vec<DecompiledTransaction>,
+}
+
+impl LeaderWindow {
+    /// Adds the block's transactions, dropping those of the previous leader group
+    pub fn push(&mut self, slot: u64, ts: i64, txs: Vec<DecompiledTransaction>) {
+        let group = slot / LEADER_GROUP_SLOTS;
+        if self.group != Some(group) {
+            self.group = Some(group);
+            self.blocks.clear();
+            self.txs.clear();
+        }
+        self.blocks.push((slot, ts));
+        self.txs.extend(txs.into_iter().filter(|tx| !tx.swaps.is_empty() || tx.tip > 0));
+    }
+
+    /// The transactions in the window, sorted by inclusion order
+    pub fn txs(&self) -> Vec<&DecompiledTransaction> {
+        let mut txs = self.txs.iter().collect::<Vec<_>>();
+        txs.sort_by_key(|tx| (tx.slot, tx.order));
+        txs
+    }
+
+    fn ts(&self, slot: u64) -> Option<i64> {
+        self.blocks.iter().find(|(s, _)| *s == slot).map(|(_, ts)| *ts)
+    }
+
+    /// Sandwiches across the slots of the window whose backrun landed in `slot`, sandwiches within a single block are
+    /// left to `find_block_sandwiches`. Each sandwich is reported once, along with the block of its backrun.
+    pub fn find_sandwiches(&self, slot: u64, rule_sets: &[RuleSet]) -> Vec<Sandwich> {
+        if self.blocks.len() < 2 {
+            return Vec::new();
+        }
+        let txs = self.txs();
+        let mut sandwiches = find_block_sandwiches(&txs, slot, 0, rule_sets);
+        sandwiches.retain(|sandwich| sandwich.is_cross_slot() && sandwich.backrun.slot == slot);
+        sandwiches.iter_mut().for_each(|sandwich| {
+            sandwich.slot = sandwich.frontrun.slot;
+            sandwich.ts = self.ts(sandwich.slot).unwrap_or_default();
+        });
+        sandwiches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::fixtures::{swap, MINT_A};
+
+    #[test]
+    fn computes_profit() {
+        let frontrun = swap(WSOL_PUBKEY, MINT_A, 1_000, 500);
+        let backrun = swap(MINT_A, WSOL_PUBKEY, 450, 1_200);
+        let profit = Profit::new(&frontrun, &backrun, 30, 20).unwrap();
+        assert_eq!((profit.gross, profit.leftover, profit.net), (200, 50, Some(150)));
+        // no net profit unless quoted in sol
+        let profit = Profit::new(&swap(MINT_A, WSOL_PUBKEY, 1_000, 500), &swap(WSOL_PUBKEY, MINT_A, 450, 1_200), 30, 20).unwrap();
+        assert_eq!((profit.gross, profit.net), (200, None));
+    }
+
+    #[test]
+    fn rejects_overflowing_profit() {
+        let frontrun = swap(WSOL_PUBKEY, MINT_A, 1, 500);
+        assert!(Profit::new(&frontrun, &swap(MINT_A, WSOL_PUBKEY, 500, u64::MAX), 0, 0).is_none());
+        assert!(Profit::new(&frontrun, &swap(MINT_A, WSOL_PUBKEY, u64::MAX, 1), 0, 0).is_none());
+        // gross fits, but not once the fees are taken out
+        assert!(Profit::new(&swap(WSOL_PUBKEY, MINT_A, i64::MAX as u64, 500), &swap(MINT_A, WSOL_PUBKEY, 500, 0), u64::MAX, 0).is_none());
+    }
+}
@@ -0,0 +1,345 @@
+use std::{collections::{BTreeMap, HashMap, HashSet}, env, fmt::Display, sync::{Arc, Mutex}, time::Duration};
+
+use dashmap::DashMap;
+use futures::{SinkExt, StreamExt};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{address_lookup_table::{state::AddressLookupTable, AddressLookupTableAccount}, commitment_config::CommitmentConfig, pubkey::Pubkey};
+use tokio::sync::mpsc;
+use yellowstone_grpc_client::GeyserGrpcBuilder;
+use yellowstone_grpc_proto::{geyser::{subscribe_update::UpdateOneof, CommitmentLevel, SubscribeRequestFilterAccounts, SubscribeRequestPing, SubscribeUpdateAccountInfo, SubscribeUpdateBlock}, prelude::{SubscribeRequest, SubscribeRequestFilterBlocks}, tonic::transport::Endpoint};
+
+use crate::{backfill::backfill, bundle::{tag_bundles, BundleSource}, cluster::SandwicherClusters, confidence::Funders, decode::{decompile, try_pubkey_from_slice, DecodeError, DecoderRegistry, DecompiledTransaction}, detect::{find_block_sandwiches, LeaderWindow, Sandwich}, mint::MintCache, price::{value_sandwiches, FallbackPrices, PriceBook}, record::RecordMessage, rules::RuleSet, storage::{DbBlock, DbMessage, SlotStatus}};
+
+/// Configuration and caches shared by everything that processes blocks, i.e. the live finder, backfill and replay
+pub struct ProcessContext {
+    pub decoders: DecoderRegistry,
+    pub fallback_prices: FallbackPrices,
+    pub mints: MintCache,
+    pub rule_sets: Vec<RuleSet>,
+    pub clusters: Mutex<SandwicherClusters>,
+    /// tags sandwiches as bundled or not if set
+    pub bundles: Option<Box<dyn BundleSource>>,
+    pub skipped: SkipCounters,
+}
+
+impl Default for ProcessContext {
+    fn default() -> Self {
+        Self {
+            decoders: DecoderRegistry::default(),
+            fallback_prices: FallbackPrices::default(),
+            mints: MintCache::default(),
+            rule_sets: vec![RuleSet::default()],
+            clusters: Mutex::new(SandwicherClusters::default()),
+            bundles: None,
+            skipped: SkipCounters::default(),
+        }
+    }
+}
+
+/// Number of transactions skipped because they couldn't be decoded, by `DecodeError::kind`
+#[derive(Default)]
+pub struct SkipCounters {
+    counts: DashMap<&'static str, u64>,
+}
+
+impl SkipCounters {
+    pub fn record(&self, e: &DecodeError) {
+        *self.counts.entry(e.kind()).or_default() += 1;
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<&'static str, u64> {
+        self.counts.iter().map(|entry| (*entry.key(), *entry.value())).collect()
+    }
+}
+
+/// Why a block couldn't be processed, the block is skipped but the pipeline keeps going
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProcessError {
+    /// sandwiches can't be dated without it
+    MissingBlockTime(u64),
+}
+
+impl Display for ProcessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingBlockTime(slot) => write!(f, "block {slot} has no block time"),
+        }
+    }
+}
+
+impl std::error::Error for ProcessError {}
+
+/// Decodes the transactions in the block and runs sandwich detection on them, as well as on the blocks of the same
+/// leader group in `window` for sandwiches across slots. Also returns the number of swaps found.
+/// Transactions that can't be decoded are skipped and counted in `ctx.skipped`.
+pub async fn process_block(block: &SubscribeUpdateBlock, rpc_client: Option<&RpcClient>, lut_cache: &DashMap<Pubkey, AddressLookupTableAccount>, ctx: &ProcessContext, window: &mut LeaderWindow) -> Result<(DbBlock, Vec<Sandwich>, usize), ProcessError> {
+    let slot = block.slot;
+    let ts = block.block_time.as_ref().ok_or(ProcessError::MissingBlockTime(slot))?.timestamp;
+    let futs = block.transactions.iter().filter_map(|tx| {
+        if tx.is_vote {
+            None
+        } else {
+            Some(decompile(tx, slot, rpc_client, lut_cache, &ctx.decoders))
+        }
+    }).collect::<Vec<_>>();
+    let mut skipped = 0;
+    let mut txs = futures::future::join_all(futs).await.into_iter().filter_map(|tx| match tx {
+        Ok(tx) => tx,
+        Err(e) => {
+            ctx.skipped.record(&e);
+            skipped += 1;
+            None
+        }
+    }).collect::<Vec<DecompiledTransaction>>();
+    if skipped > 0 {
+        println!("{skipped} undecodable txs skipped in block {slot}");
+    }
+    txs.iter_mut().for_each(|tx| ctx.mints.update(&mut tx.swaps));
+    txs.sort_by_key(|x| x.order);
+    let block_txs = txs.iter().collect::<Vec<&DecompiledTransaction>>();
+    let swap_count = block_txs.iter().map(|tx| tx.swaps.len()).sum::<usize>();
+    let mut sandwiches = find_block_sandwiches(&block_txs, slot, ts, &ctx.rule_sets);
+    value_sandwiches(&mut sandwiches, &PriceBook::new(&block_txs, &ctx.fallback_prices));
+    ctx.clusters.lock().unwrap().assign(&mut sandwiches, &Funders::new(&block_txs));
+    window.push(slot, ts, txs);
+    let window_txs = window.txs();
+    let mut cross_slot_sandwiches = window.find_sandwiches(slot, &ctx.rule_sets);
+    value_sandwiches(&mut cross_slot_sandwiches, &PriceBook::new(&window_txs, &ctx.fallback_prices));
+    ctx.clusters.lock().unwrap().assign(&mut cross_slot_sandwiches, &Funders::new(&window_txs));
+    sandwiches.extend(cross_slot_sandwiches);
+    if let Some(bundles) = &ctx.bundles {
+        tag_bundles(&mut sandwiches, bundles.as_ref());
+    }
+    Ok((DbBlock {
+        slot,
+        ts,
+        tx_count: block.transactions.len(),
+    }, sandwiches, swap_count))
+}
+
+/// The block's slot as observed, and the slots between it and its parent as skipped
+pub fn slot_statuses(block: &SubscribeUpdateBlock) -> Vec<(u64, SlotStatus)> {
+    let mut statuses = (block.parent_slot + 1..block.slot).map(|slot| (slot, SlotStatus::Skipped)).collect::<Vec<_>>();
+    statuses.push((block.slot, SlotStatus::Observed));
+    statuses
+}
+
+/// Caches the updated lut, returns its key unless the update was rejected or isn't a valid lut
+pub fn apply_lut_update(lut_cache: &DashMap<Pubkey, AddressLookupTableAccount>, account_info: &SubscribeUpdateAccountInfo) -> Option<Pubkey> {
+    let key = try_pubkey_from_slice(&account_info.pubkey).ok()?;
+    let lut = AddressLookupTable::deserialize(&account_info.data).ok()?;
+    // refuse to shorten luts
+    if let Some(existing_entry) = lut_cache.get(&key) {
+        let existing_len = existing_entry.addresses.len();
+        if existing_len > lut.addresses.len() {
+            return None;
+        }
+    }
+    lut_cache.insert(key, AddressLookupTableAccount {
+        key,
+        addresses: lut.addresses.to_vec(),
+    });
+    Some(key)
+}
+
+/// The cached luts referenced by the transactions in the block
+fn block_luts(block: &SubscribeUpdateBlock, lut_cache: &DashMap<Pubkey, AddressLookupTableAccount>) -> Vec<AddressLookupTableAccount> {
+    let lut_keys = block.transactions.iter().filter_map(|tx| tx.transaction.as_ref()?.message.as_ref()).flat_map(|msg| {
+        msg.address_table_lookups.iter().filter_map(|lookup| try_pubkey_from_slice(&lookup.account_key).ok())
+    }).collect::<HashSet<Pubkey>>();
+    lut_keys.iter().filter_map(|key| lut_cache.get(key).map(|lut| lut.value().clone())).collect()
+}
+
+/// Number of blocks fetched in parallel when backfilling the slots missed while disconnected
+const GAP_BACKFILL_CONCURRENCY: usize = 4;
+/// Most recent slots of a gap that are backfilled on reconnect, about an hour. Older ones are marked as missed,
+/// to be picked up by the backfill subcommand.
+const MAX_GAP_BACKFILL_SLOTS: u64 = 9000;
+
+/// Delay before reconnecting, doubled after each failed attempt up to `MAX_RECONNECT_DELAY`
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(120);
+
+/// Why `sandwich_finder_loop` returned
+pub enum Disconnect {
+    /// connecting or subscribing failed
+    Connect,
+    /// the subscription ended or failed after it was established
+    Stream,
+    /// the receiving end of the named channel is gone, i.e. the finder is shutting down
+    Shutdown(&'static str),
+}
+
+/// `last_slot` is the latest slot in the db, it's then kept across reconnects so that the slots missed in between,
+/// including those missed while the finder wasn't running, are backfilled.
+/// Runs until one of the receivers is dropped.
+pub async fn sandwich_finder(sender: mpsc::Sender<Sandwich>, db_sender: mpsc::Sender<DbMessage>, ctx: Arc<ProcessContext>, record_sender: Option<mpsc::Sender<RecordMessage>>, mut last_slot: Option<u64>) {
+    let mut delay = RECONNECT_DELAY;
+    loop {
+        let disconnect = sandwich_finder_loop(sender.clone(), db_sender.clone(), &ctx, record_sender.as_ref(), &mut last_slot).await;
+        match disconnect {
+            Disconnect::Shutdown(channel) => {
+                println!("{channel} channel closed, stopping the finder");
+                return;
+            }
+            Disconnect::Stream => delay = RECONNECT_DELAY,
+            Disconnect::Connect => {}
+        }
+        println!("reconnecting in {}s", delay.as_secs());
+        tokio::time::sleep(delay).await;
+        if matches!(disconnect, Disconnect::Connect) {
+            delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+        }
+    }
+}
+
+pub async fn sandwich_finder_loop(sender: mpsc::Sender<Sandwich>, db_sender: mpsc::Sender<DbMessage>, ctx: &Arc<ProcessContext>, record_sender: Option<&mpsc::Sender<RecordMessage>>, last_slot: &mut Option<u64>) -> Disconnect {
+    let rpc_url = env::var("RPC_URL").expect("RPC_URL is not set");
+    let grpc_url = env::var("GRPC_URL").expect("GRPC_URL is not set");
+    let rpc_client = RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::processed());
+    let lut_cache = DashMap::new();
+    let mut window = LeaderWindow::default();
+    println!("connecting to grpc server: {}", grpc_url);
+    let grpc_client = GeyserGrpcBuilder{
+        endpoint: Endpoint::from_shared(grpc_url.to_string()).unwrap(),
+        x_token: None,
+        x_request_snapshot: false,
+        send_compressed: None,
+        accept_compressed: None,
+        max_decoding_message_size: Some(128 * 1024 * 1024),
+        max_encoding_message_size: None,
+    }.connect().await;
+    let mut grpc_client = match grpc_client {
+        Ok(grpc_client) => grpc_client,
+        Err(e) => {
+            println!("unable to connect to grpc server: {e}");
+            return Disconnect::Connect;
+        }
+    };
+    println!("connected to grpc server!");
+    let mut blocks = HashMap::new();
+    blocks.insert("client".to_string(), SubscribeRequestFilterBlocks {
+        account_include: vec![],
+        include_transactions: Some(true),
+        include_accounts: Some(true),
+        include_entries: Some(false),
+    });
+    let mut accounts = HashMap::new();
+    accounts.insert("client".to_string(), SubscribeRequestFilterAccounts {
+        account: vec![],
+        owner: vec!["AddressLookupTab1e1111111111111111111111111".to_string()],
+        filters: vec![],
+        nonempty_txn_signature: Some(true),
+    });
+    let subscription = grpc_client.subscribe_with_request(Some(SubscribeRequest {
+        accounts,
+        blocks,
+        commitment: Some(CommitmentLevel::Confirmed as i32),
+        ..Default::default()
+    })).await;
+    let (mut sink, mut stream) = match subscription {
+        Ok(subscription) => subscription,
+        Err(e) => {
+            println!("unable to subscribe: {e}");
+            return Disconnect::Connect;
+        }
+    };
+    println!("subscription request sent!");
+    // the first block on this connection tells us which slots were missed since the last one
+    let mut first_block = true;
+    while let Some(msg) = stream.next().await {
+        if msg.is_err() {
+            println!("grpc error: {:?}", msg.err());
+            break;
+        }
+        let msg = msg.unwrap();
+        match msg.update_oneof {
+            Some(UpdateOneof::Block(block)) => {
+                println!("new block {}, {} txs", block.slot, block.transactions.len());
+                if first_block {
+                    first_block = false;
+                    if let Some(last_slot) = *last_slot {
+                        if block.slot > last_slot + 1 {
+                            let end = block.slot - 1;
+                            let start = (last_slot + 1).max(block.slot.saturating_sub(MAX_GAP_BACKFILL_SLOTS));
+                            println!("slots {}-{} were missed since the last block, backfilling {start}-{end}", last_slot + 1, end);
+                            if start > last_slot + 1 {
+                                println!("gap exceeds {MAX_GAP_BACKFILL_SLOTS} slots, marking slots {}-{} as missed, use the backfill subcommand for them", last_slot + 1, start - 1);
+                            }
+                            let rpc_url = rpc_url.clone();
+                            let ctx = ctx.clone();
+                            let db_sender = db_sender.clone();
+                            tokio::spawn(async move {
+                                for slot in last_slot + 1..start {
+                                    if db_sender.send(DbMessage::Slot(slot, SlotStatus::Missed)).await.is_err() {
+                                        return;
+                                    }
+                                }
+                                let rpc_client = RpcClient::new_with_commitment(rpc_url, CommitmentConfig::confirmed());
+                                backfill(&rpc_client, start, end, &HashSet::new(), GAP_BACKFILL_CONCURRENCY, &ctx, db_sender).await;
+                            });
+                        }
+                    }
+                }
+                let now = std::time::Instant::now();
+                match process_block(&block, Some(&rpc_client), &lut_cache, ctx, &mut window).await {
+                    Ok((db_block, sandwiches, swap_count)) => {
+                        let bundle_count = sandwiches.len();
+                        if db_sender.send(DbMessage::Block(db_block, sandwiches.clone())).await.is_err() {
+                            return Disconnect::Shutdown("db");
+                        }
+                        sandwiches.into_iter().for_each(|sandwich| {
+                            let sender = sender.clone();
+                            tokio::spawn(async move {
+                                // only fails once the receiving loop has stopped, i.e. on shutdown
+                                let _ = sender.send(sandwich).await;
+                            });
+                        });
+                        println!("block {} processed in {}us, {} swaps found, {} bundles found", block.slot, now.elapsed().as_micros(), swap_count, bundle_count);
+                        for (slot, status) in slot_statuses(&block) {
+                            if db_sender.send(DbMessage::Slot(slot, status)).await.is_err() {
+                                return Disconnect::Shutdown("db");
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        println!("skipping block {}: {e}", block.slot);
+                        // not analysed, so it's left for a backfill rather than counted as observed
+                        if db_sender.send(DbMessage::Slot(block.slot, SlotStatus::Missed)).await.is_err() {
+                            return Disconnect::Shutdown("db");
+                        }
+                    }
+                }
+                if last_slot.is_none_or(|last_slot| block.slot > last_slot) {
+                    *last_slot = Some(block.slot);
+                }
+                if let Some(record_sender) = record_sender {
+                    let luts = block_luts(&block, &lut_cache);
+                    if record_sender.send(RecordMessage::Block(block, luts)).await.is_err() {
+                        return Disconnect::Shutdown("record");
+                    }
+                }
+            }
+            Some(UpdateOneof::Account(account)) => {
+                if let Some(account_info) = account.account {
+                    if let Some(key) = apply_lut_update(&lut_cache, &account_info) {
+                        println!("lut updated: {:?}", key);
+                    }
+                    if let Some(record_sender) = record_sender {
+                        if record_sender.send(RecordMessage::Account(account_info, account.slot)).await.is_err() {
+                            return Disconnect::Shutdown("record");
+                        }
+                    }
+                }
+            }
+            Some(UpdateOneof::Ping(_)) => {
+                let _ = sink.send(SubscribeRequest {
+                    ping: Some(SubscribeRequestPing {id: 1}),
+                    ..Default::default()
+                }).await;
+            }
+            _ => {}
+        }
+    }
+    Disconnect::Stream
+}
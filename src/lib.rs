@@ -0,0 +1,19 @@
+pub mod amm;
+pub mod api;
+pub mod backfill;
+pub mod bundle;
+pub mod cluster;
+pub mod codec;
+pub mod confidence;
+pub mod decode;
+pub mod detect;
+pub mod finder;
+pub mod migrate;
+pub mod mint;
+pub mod price;
+pub mod record;
+pub mod replay;
+pub mod report;
+pub mod rules;
+pub mod spool;
+pub mod storage;
@@ -0,0 +1,128 @@
+use std::fmt::Display;
+
+use crate::storage::{Backend, SandwichStore, StorageError};
+
+/// A schema change embedded in the binary, applied once and in order of version, written once per backend
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub mysql: &'static str,
+    pub postgres: &'static str,
+    pub sqlite: &'static str,
+}
+
+impl Migration {
+    pub fn sql(&self, backend: Backend) -> &'static str {
+        match backend {
+            Backend::Mysql => self.mysql,
+            Backend::Postgres => self.postgres,
+            Backend::Sqlite => self.sqlite,
+        }
+    }
+}
+
+/// Every migration so far, new ones are appended with the next version
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        mysql: include_str!("../migrations/mysql/0001_initial.sql"),
+        postgres: include_str!("../migrations/postgres/0001_initial.sql"),
+        sqlite: include_str!("../migrations/sqlite/0001_initial.sql"),
+    },
+];
+
+/// Latest version of the schema known to this binary
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+#[derive(Debug)]
+pub enum MigrationError {
+    Storage(StorageError),
+    /// a statement of a migration failed, the migrations before it are kept
+    Failed { version: u32, name: &'static str, error: StorageError },
+    /// the db has migrations that haven't been applied
+    Pending { current: u32, latest: u32 },
+    /// the db was migrated by a newer binary
+    Unknown { current: u32, latest: u32 },
+}
+
+impl Display for MigrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Storage(e) => write!(f, "{e}"),
+            Self::Failed { version, name, error } => write!(f, "migration {version} ({name}) failed: {error}"),
+            Self::Pending { current, latest } => write!(f, "the db schema is at version {current} but {latest} is expected, run the migrate subcommand"),
+            Self::Unknown { current, latest } => write!(f, "the db schema is at version {current}, newer than the latest known version {latest}"),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+impl From<StorageError> for MigrationError {
+    fn from(e: StorageError) -> Self {
+        Self::Storage(e)
+    }
+}
+
+/// Applies the migrations newer than the db's version, returns those applied
+pub fn migrate(store: &mut dyn SandwichStore) -> Result<Vec<&'static Migration>, MigrationError> {
+    let current = store.schema_version()?;
+    let mut applied = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|migration| migration.version > current) {
+        store.apply_migration(migration).map_err(|error| MigrationError::Failed {
+            version: migration.version,
+            name: migration.name,
+            error,
+        })?;
+        applied.push(migration);
+    }
+    Ok(applied)
+}
+
+/// Checks that the db's schema is the one this binary expects
+pub fn check(store: &mut dyn SandwichStore) -> Result<(), MigrationError> {
+    let (current, latest) = (store.schema_version()?, latest_version());
+    if current < latest {
+        Err(MigrationError::Pending { current, latest })
+    } else if current > latest {
+        Err(MigrationError::Unknown { current, latest })
+    } else {
+        Ok(())
+    }
+}
+
+/// The statements of a migration without comments, separated by a `;` at the end of a line.
+/// Only needed for mysql, whose client runs one statement at a time.
+pub(crate) fn statements(sql: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut statement = String::new();
+    for line in sql.lines().map(|line| line.trim_end()).filter(|line| !line.trim_start().starts_with("--")) {
+        statement.push_str(line);
+        statement.push('\n');
+        if line.ends_with(';') {
+            statements.push(std::mem::take(&mut statement));
+        }
+    }
+    if !statement.trim().is_empty() {
+        statements.push(statement);
+    }
+    statements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_mysql_statements() {
+        for migration in MIGRATIONS {
+            let statements = statements(migration.mysql);
+            assert!(!statements.is_empty());
+            assert!(statements.iter().all(|statement| statement.trim_end().ends_with(';') && !statement.contains("--")));
+        }
+        assert_eq!(statements("-- comment\nselect 1,\n  2;\nselect 3;\n"), ["select 1,\n  2;\n", "select 3;\n"]);
+    }
+}
@@ -0,0 +1,120 @@
+use std::{collections::HashMap, path::Path};
+
+use dashmap::DashMap;
+
+use crate::decode::{config::load_file, Swap, WSOL_PUBKEY};
+
+/// Mints whose decimals are cached, the least recently seen half is dropped once it's exceeded
+const MAX_CACHED_MINTS: usize = 100_000;
+
+/// Decimals of the mints seen recently, along with symbols loaded from a TOML or JSON file mapping mints to their symbols,
+/// e.g. `EPjFWdd5AufqSSqeM2qFtw5Ux8tKoRRwoVwPKsnUk3ii = "USDC"`
+pub struct MintCache {
+    /// decimals of each mint with the latest slot it was seen in
+    decimals: DashMap<String, (u8, u64)>,
+    symbols: HashMap<String, String>,
+    capacity: usize,
+}
+
+impl Default for MintCache {
+    fn default() -> Self {
+        Self {
+            decimals: DashMap::new(),
+            symbols: HashMap::new(),
+            capacity: MAX_CACHED_MINTS,
+        }
+    }
+}
+
+impl MintCache {
+    pub fn with_symbols(path: &Path) -> Result<Self, String> {
+        Ok(Self {
+            symbols: load_file(path)?,
+            ..Default::default()
+        })
+    }
+
+    pub fn decimals(&self, mint: &str) -> Option<u8> {
+        if mint == WSOL_PUBKEY.to_string() {
+            return Some(9);
+        }
+        self.decimals.get(mint).map(|entry| entry.0)
+    }
+
+    pub fn symbol(&self, mint: &str) -> Option<&str> {
+        self.symbols.get(mint).map(|symbol| symbol.as_str())
+    }
+
+    /// The decimals of the mint, from the swap if it has them or the cache otherwise, marking the mint as seen in the slot
+    fn lookup(&self, mint: &str, decimals: Option<u8>, slot: u64) -> Option<u8> {
+        if let Some(decimals) = decimals {
+            self.decimals.entry(mint.to_string()).and_modify(|entry| *entry = (decimals, entry.1.max(slot))).or_insert((decimals, slot));
+            return Some(decimals);
+        }
+        if let Some(mut entry) = self.decimals.get_mut(mint) {
+            entry.1 = entry.1.max(slot);
+            return Some(entry.0);
+        }
+        self.decimals(mint)
+    }
+
+    /// Drops the least recently seen half of the mints once there are more than `capacity`
+    fn evict(&self) {
+        if self.decimals.len() <= self.capacity {
+            return;
+        }
+        let mut slots = self.decimals.iter().map(|entry| entry.1).collect::<Vec<_>>();
+        let kept = self.capacity / 2;
+        let index = slots.len() - kept.max(1);
+        let (_, cutoff, _) = slots.select_nth_unstable(index);
+        let cutoff = *cutoff;
+        self.decimals.retain(|_, (_, slot)| *slot >= cutoff);
+    }
+
+    /// Caches the decimals found in the swaps' transactions, and fills in the decimals and symbols missing from them
+    pub fn update(&self, swaps: &mut [Swap]) {
+        swaps.iter_mut().for_each(|swap| {
+            let input_decimals = self.lookup(&swap.input_mint, swap.input_decimals, swap.slot);
+            let output_decimals = self.lookup(&swap.output_mint, swap.output_decimals, swap.slot);
+            swap.set_decimals(input_decimals, output_decimals);
+            swap.input_symbol = self.symbol(&swap.input_mint).map(|symbol| symbol.to_string());
+            swap.output_symbol = self.symbol(&swap.output_mint).map(|symbol| symbol.to_string());
+        });
+        self.evict();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::fixtures::{swap, MINT_A, MINT_B};
+
+    fn seen(mints: &MintCache, mint: &str, decimals: Option<u8>, slot: u64) -> Option<u8> {
+        let mut swaps = [Swap { input_mint: mint.to_string(), input_decimals: decimals, slot, ..swap(MINT_A, MINT_B, 1, 1) }];
+        mints.update(&mut swaps);
+        swaps[0].input_decimals
+    }
+
+    #[test]
+    fn fills_in_cached_decimals() {
+        let mints = MintCache::default();
+        assert_eq!(seen(&mints, "a", None, 1), None);
+        assert_eq!(seen(&mints, "a", Some(6), 2), Some(6));
+        assert_eq!(seen(&mints, "a", None, 3), Some(6));
+        assert_eq!(mints.decimals(&WSOL_PUBKEY.to_string()), Some(9));
+    }
+
+    #[test]
+    fn evicts_least_recently_seen_mints() {
+        let mints = MintCache { capacity: 4, ..Default::default() };
+        for (slot, mint) in ["a", "b", "c", "d"].iter().enumerate() {
+            seen(&mints, mint, Some(6), slot as u64);
+        }
+        // a is seen again, so b and c are the oldest once e goes over capacity
+        seen(&mints, "a", None, 10);
+        seen(&mints, "e", Some(6), 11);
+        assert!(mints.decimals.len() <= 4);
+        assert_eq!((mints.decimals("a"), mints.decimals("e")), (Some(6), Some(6)));
+        assert_eq!((mints.decimals("b"), mints.decimals("c")), (None, None));
+    }
+}
@@ -0,0 +1,81 @@
+use std::{collections::HashMap, path::Path};
+
+use crate::{decode::{config::load_file, DecompiledTransaction, WSOL_PUBKEY}, detect::Sandwich};
+
+const LAMPORTS_PER_SOL: f64 = 1_000_000_000.0;
+
+/// Prices used for mints that weren't traded against wsol in the block, loaded from a TOML or JSON file mapping
+/// mints to their price in lamports per base unit, e.g. `EPjFWdd5AufqSSqeM2qFtw5Ux8tKoRRwoVwPKsnUk3ii = 6.9`
+#[derive(Default)]
+pub struct FallbackPrices {
+    prices: HashMap<String, f64>,
+}
+
+impl FallbackPrices {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let prices: HashMap<String, f64> = load_file(path)?;
+        if let Some((mint, price)) = prices.iter().find(|(_, price)| !(price.is_finite() && **price >= 0.0)) {
+            return Err(format!("{mint}: invalid price {price}"));
+        }
+        Ok(Self { prices })
+    }
+}
+
+/// Prices in lamports per base unit as seen in a single block
+pub struct PriceBook<'a> {
+    prices: HashMap<&'a str, f64>,
+    fallback: &'a FallbackPrices,
+}
+
+impl<'a> PriceBook<'a> {
+    /// Takes the price of each mint from its last swap against wsol in the block.
+    /// `block_txs` is expected to be sorted by inclusion order.
+    pub fn new(block_txs: &[&'a DecompiledTransaction], fallback: &'a FallbackPrices) -> Self {
+        let wsol = WSOL_PUBKEY.to_string();
+        let mut prices = HashMap::new();
+        block_txs.iter().flat_map(|tx| tx.swaps.iter()).for_each(|swap| {
+            if swap.input_amount == 0 || swap.output_amount == 0 {
+                return;
+            }
+            if swap.input_mint == wsol {
+                prices.insert(swap.output_mint.as_str(), swap.input_amount as f64 / swap.output_amount as f64);
+            } else if swap.output_mint == wsol {
+                prices.insert(swap.input_mint.as_str(), swap.output_amount as f64 / swap.input_amount as f64);
+            }
+        });
+        Self {
+            prices,
+            fallback,
+        }
+    }
+
+    /// Lamports per base unit of the mint
+    pub fn price(&self, mint: &str) -> Option<f64> {
+        if mint == WSOL_PUBKEY.to_string() {
+            return Some(1.0);
+        }
+        self.prices.get(mint).or_else(|| self.fallback.prices.get(mint)).copied()
+    }
+
+    /// Value of the amount in sol
+    pub fn value_sol(&self, mint: &str, amount: f64) -> Option<f64> {
+        self.price(mint).map(|price| amount * price / LAMPORTS_PER_SOL)
+    }
+}
+
+/// Fills in `loss_sol` and `profit_sol` of the sandwiches, left as `None` where a price or the loss estimate is unavailable
+pub fn value_sandwiches(sandwiches: &mut [Sandwich], prices: &PriceBook) {
+    sandwiches.iter_mut().for_each(|sandwich| {
+        sandwich.loss_sol = sandwich.estimate_victim_losses().ok().and_then(|losses| {
+            sandwich.victim.iter().zip(losses).map(|(victim, loss)| prices.value_sol(&victim.output_mint, loss.output as f64)).sum()
+        });
+        let profit = &sandwich.profit;
+        let gross = prices.value_sol(&profit.quote_mint, profit.gross as f64);
+        // usually sold back entirely, no need for a price then
+        let leftover = match profit.leftover {
+            0 => Some(0.0),
+            leftover => prices.value_sol(&sandwich.frontrun.output_mint, leftover as f64),
+        };
+        sandwich.profit_sol = gross.zip(leftover).map(|(gross, leftover)| gross + leftover - (profit.fees + profit.tips) as f64 / LAMPORTS_PER_SOL);
+    });
+}
@@ -0,0 +1,182 @@
+use std::{collections::HashMap, fs::{self, File, OpenOptions}, io::{self, BufRead, BufReader, BufWriter, Write}, path::{Path, PathBuf}};
+
+use prost::Message;
+use solana_sdk::{address_lookup_table::{state::LOOKUP_TABLE_META_SIZE, AddressLookupTableAccount}, pubkey::Pubkey};
+use tokio::sync::mpsc;
+use yellowstone_grpc_proto::geyser::{SubscribeUpdateAccountInfo, SubscribeUpdateBlock};
+
+use crate::decode::try_pubkey_from_slice;
+
+pub const INDEX_FILE: &str = "index.csv";
+pub const BLOCKS_DIR: &str = "blocks";
+pub const LUTS_DIR: &str = "luts";
+
+pub enum RecordMessage {
+    /// A block along with the luts its transactions reference
+    Block(SubscribeUpdateBlock, Vec<AddressLookupTableAccount>),
+    /// A lut update along with the slot it was received at
+    Account(SubscribeUpdateAccountInfo, u64),
+}
+
+type SegmentWriter = zstd::stream::AutoFinishEncoder<'static, BufWriter<File>>;
+
+struct Segment {
+    blocks: SegmentWriter,
+    luts: SegmentWriter,
+    block_path: String,
+    block_count: usize,
+    /// address count of the luts written to this segment
+    written_luts: HashMap<Pubkey, usize>,
+}
+
+/// Writes the updates received by the finder into zstd compressed segments of length-delimited protobuf messages:
+/// `blocks/blocks-<first slot>.pb.zst` for `SubscribeUpdateBlock`s and `luts/luts-<first slot>.pb.zst` for the
+/// `SubscribeUpdateAccountInfo`s needed to replay them. `index.csv` maps each recorded slot to its block segment.
+/// Segments are flushed before they're indexed, the last one is left without a zstd frame end if the recorder crashes.
+pub struct Recorder {
+    dir: PathBuf,
+    max_segment_blocks: usize,
+    segment: Option<Segment>,
+    index: BufWriter<File>,
+}
+
+fn open_writer(path: &Path) -> io::Result<SegmentWriter> {
+    Ok(zstd::Encoder::new(BufWriter::new(File::create(path)?), 0)?.auto_finish())
+}
+
+/// Account data that deserializes into the given lut, for luts fetched over rpc where the raw account isn't kept
+fn lut_account_info(lut: &AddressLookupTableAccount) -> SubscribeUpdateAccountInfo {
+    let mut data = vec![0u8; LOOKUP_TABLE_META_SIZE];
+    // ProgramState::LookupTable, never deactivated
+    data[0..4].copy_from_slice(&1u32.to_le_bytes());
+    data[4..12].copy_from_slice(&u64::MAX.to_le_bytes());
+    lut.addresses.iter().for_each(|address| data.extend_from_slice(address.as_ref()));
+    SubscribeUpdateAccountInfo {
+        pubkey: lut.key.to_bytes().to_vec(),
+        data,
+        ..Default::default()
+    }
+}
+
+impl Recorder {
+    pub fn new(dir: &Path, max_segment_blocks: usize) -> io::Result<Self> {
+        fs::create_dir_all(dir.join(BLOCKS_DIR))?;
+        fs::create_dir_all(dir.join(LUTS_DIR))?;
+        let index = OpenOptions::new().create(true).append(true).open(dir.join(INDEX_FILE))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            max_segment_blocks,
+            segment: None,
+            index: BufWriter::new(index),
+        })
+    }
+
+    fn segment(&mut self, slot: u64) -> io::Result<&mut Segment> {
+        if self.segment.as_ref().is_some_and(|segment| segment.block_count >= self.max_segment_blocks) {
+            // dropping the writers finishes the zstd frames
+            self.segment = None;
+        }
+        if self.segment.is_none() {
+            let block_path = format!("{BLOCKS_DIR}/blocks-{slot:012}.pb.zst");
+            let lut_path = format!("{LUTS_DIR}/luts-{slot:012}.pb.zst");
+            println!("recording to {block_path}");
+            self.segment = Some(Segment {
+                blocks: open_writer(&self.dir.join(&block_path))?,
+                luts: open_writer(&self.dir.join(&lut_path))?,
+                block_path,
+                block_count: 0,
+                written_luts: HashMap::new(),
+            });
+        }
+        Ok(self.segment.as_mut().unwrap())
+    }
+
+    pub fn record_block(&mut self, block: &SubscribeUpdateBlock, luts: &[AddressLookupTableAccount]) -> io::Result<()> {
+        let segment = self.segment(block.slot)?;
+        // make sure each segment can be replayed on its own
+        for lut in luts {
+            if segment.written_luts.get(&lut.key).is_some_and(|len| *len >= lut.addresses.len()) {
+                continue;
+            }
+            segment.luts.write_all(&lut_account_info(lut).encode_length_delimited_to_vec())?;
+            segment.written_luts.insert(lut.key, lut.addresses.len());
+        }
+        segment.blocks.write_all(&block.encode_length_delimited_to_vec())?;
+        segment.block_count += 1;
+        // flushing ends the current zstd block, so everything the index points at can be decoded even if the
+        // frame is never finished
+        segment.luts.flush()?;
+        segment.blocks.flush()?;
+        let line = format!("{},{}\n", block.slot, segment.block_path);
+        self.index.write_all(line.as_bytes())?;
+        self.index.flush()
+    }
+
+    /// Luts are written to the current segment, or to the next one if no block has been recorded yet
+    pub fn record_account(&mut self, account_info: &SubscribeUpdateAccountInfo, slot: u64) -> io::Result<()> {
+        let segment = self.segment(slot)?;
+        segment.luts.write_all(&account_info.encode_length_delimited_to_vec())?;
+        let len = account_info.data.len().saturating_sub(LOOKUP_TABLE_META_SIZE) / 32;
+        if let Ok(key) = try_pubkey_from_slice(&account_info.pubkey) {
+            segment.written_luts.insert(key, len);
+        }
+        Ok(())
+    }
+}
+
+/// Reads `index.csv` of a recording into (slot, block segment path) pairs
+pub fn read_index(dir: &Path) -> io::Result<Vec<(u64, PathBuf)>> {
+    let reader = BufReader::new(File::open(dir.join(INDEX_FILE))?);
+    let mut index = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if let Some((slot, path)) = line.split_once(',') {
+            if let Ok(slot) = slot.parse() {
+                index.push((slot, dir.join(path)));
+            }
+        }
+    }
+    Ok(index)
+}
+
+/// Drains the receiver into the recorder, meant to be run on a blocking thread
+pub fn record(mut receiver: mpsc::Receiver<RecordMessage>, mut recorder: Recorder) {
+    while let Some(msg) = receiver.blocking_recv() {
+        let (slot, result) = match msg {
+            RecordMessage::Block(block, luts) => (block.slot, recorder.record_block(&block, &luts)),
+            RecordMessage::Account(account_info, slot) => (slot, recorder.record_account(&account_info, slot)),
+        };
+        if let Err(e) = result {
+            println!("unable to record slot {slot}: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::DelimitedReader;
+
+    #[test]
+    fn indexed_blocks_survive_a_crash() {
+        let dir = std::env::temp_dir().join(format!("sandwich-finder-record-{}", std::process::id()));
+        let mut recorder = Recorder::new(&dir, 10).unwrap();
+        for slot in [100, 101] {
+            let block = SubscribeUpdateBlock {
+                slot,
+                ..Default::default()
+            };
+            recorder.record_block(&block, &[]).unwrap();
+        }
+        // the segment is never finished, as if the process was killed
+        std::mem::forget(recorder);
+        let index = read_index(&dir).unwrap();
+        assert_eq!(index.iter().map(|(slot, _)| *slot).collect::<Vec<_>>(), vec![100, 101]);
+        let decoder = zstd::Decoder::new(File::open(&index[0].1).unwrap()).unwrap();
+        let mut blocks = DelimitedReader::<_, SubscribeUpdateBlock>::new(decoder);
+        assert_eq!(blocks.next().unwrap().unwrap().slot, 100);
+        assert_eq!(blocks.next().unwrap().unwrap().slot, 101);
+        assert_eq!(blocks.next().unwrap().unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
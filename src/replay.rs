@@ -0,0 +1,139 @@
+use std::{fs::{self, File}, io::{self, BufReader, Read}, path::{Path, PathBuf}};
+
+use dashmap::DashMap;
+use solana_sdk::{address_lookup_table::AddressLookupTableAccount, pubkey::Pubkey};
+use tokio::sync::mpsc;
+use yellowstone_grpc_proto::geyser::{SubscribeUpdateAccountInfo, SubscribeUpdateBlock};
+
+use crate::{codec::DelimitedReader, detect::{LeaderWindow, Sandwich}, finder::{apply_lut_update, process_block, slot_statuses, ProcessContext}, record::{read_index, INDEX_FILE, LUTS_DIR}, storage::DbMessage};
+
+/// The files to replay, sorted by name if `path` is a directory
+pub fn block_files(path: &Path) -> io::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = fs::read_dir(path)?.filter_map(|entry| {
+        let path = entry.ok()?.path();
+        if path.is_file() {
+            Some(path)
+        } else {
+            None
+        }
+    }).collect::<Vec<_>>();
+    files.sort();
+    Ok(files)
+}
+
+/// (block file, lut file) pairs to replay.
+/// A recording made by `Recorder` is replayed segment by segment along with the luts recorded for each segment,
+/// and `slot_range` is used to skip the segments without any slot in range.
+pub fn replay_segments(path: &Path, slot_range: Option<(u64, u64)>) -> io::Result<Vec<(PathBuf, Option<PathBuf>)>> {
+    if !path.join(INDEX_FILE).is_file() {
+        return Ok(block_files(path)?.into_iter().map(|file| (file, None)).collect());
+    }
+    let mut segments: Vec<PathBuf> = Vec::new();
+    for (slot, segment) in read_index(path)? {
+        if slot_range.is_some_and(|(start, end)| slot < start || slot > end) {
+            continue;
+        }
+        if !segments.contains(&segment) {
+            segments.push(segment);
+        }
+    }
+    Ok(segments.into_iter().map(|segment| {
+        let file_name = segment.file_name().unwrap_or_default().to_string_lossy().replacen("blocks-", "luts-", 1);
+        let luts = path.join(LUTS_DIR).join(file_name);
+        let luts = if luts.is_file() { Some(luts) } else { None };
+        (segment, luts)
+    }).collect())
+}
+
+/// A segment that ends mid-message or mid-frame, e.g. the last one of a recorder that crashed
+fn is_truncated(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::UnexpectedEof
+}
+
+/// Opens the file for reading, decompressing it if it's zstd compressed
+fn open_segment(path: &Path) -> io::Result<Box<dyn Read + Send>> {
+    let reader = BufReader::new(File::open(path)?);
+    if path.extension().is_some_and(|ext| ext == "zst") {
+        Ok(Box::new(zstd::Decoder::with_buffer(reader)?))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Populates the lut cache from a file of length-delimited `SubscribeUpdateAccountInfo` messages.
+/// Returns the number of luts loaded, a truncated file is loaded up to its last complete message.
+pub fn load_lut_snapshot(path: &Path, lut_cache: &DashMap<Pubkey, AddressLookupTableAccount>) -> io::Result<usize> {
+    let mut count = 0;
+    for account_info in DelimitedReader::<_, SubscribeUpdateAccountInfo>::new(open_segment(path)?) {
+        let account_info = match account_info {
+            Ok(account_info) => account_info,
+            Err(e) if is_truncated(&e) => {
+                eprintln!("{} is truncated, loaded up to its last complete lut: {e}", path.display());
+                break;
+            }
+            Err(e) => return Err(e),
+        };
+        if apply_lut_update(lut_cache, &account_info).is_some() {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Runs the blocks recorded at `path` (length-delimited `SubscribeUpdateBlock` messages) through the same pipeline as the live finder.
+/// Luts are only resolved from `lut_cache`, and sandwiches are emitted in block order so that replays are deterministic.
+/// A truncated segment is replayed up to its last complete block.
+pub async fn replay(path: &Path, slot_range: Option<(u64, u64)>, lut_cache: &DashMap<Pubkey, AddressLookupTableAccount>, ctx: &ProcessContext, sender: mpsc::Sender<Sandwich>, db_sender: Option<mpsc::Sender<DbMessage>>) -> io::Result<()> {
+    let mut window = LeaderWindow::default();
+    for (file, luts) in replay_segments(path, slot_range)? {
+        if let Some(luts) = luts {
+            load_lut_snapshot(&luts, lut_cache)?;
+        }
+        eprintln!("replaying {}", file.display());
+        for block in DelimitedReader::<_, SubscribeUpdateBlock>::new(open_segment(&file)?) {
+            let block = match block {
+                Ok(block) => block,
+                Err(e) if is_truncated(&e) => {
+                    eprintln!("{} is truncated, replayed up to its last complete block: {e}", file.display());
+                    break;
+                }
+                Err(e) => return Err(e),
+            };
+            if slot_range.is_some_and(|(start, end)| block.slot < start || block.slot > end) {
+                continue;
+            }
+            let (db_block, sandwiches, swap_count) = match process_block(&block, None, lut_cache, ctx, &mut window).await {
+                Ok(processed) => processed,
+                Err(e) => {
+                    eprintln!("skipping block {}: {e}", block.slot);
+                    continue;
+                }
+            };
+            eprintln!("block {}, {} txs, {} swaps found, {} bundles found", block.slot, block.transactions.len(), swap_count, sandwiches.len());
+            if let Some(db_sender) = &db_sender {
+                if db_sender.send(DbMessage::Block(db_block, sandwiches.clone())).await.is_err() {
+                    eprintln!("db channel closed, stopping the replay");
+                    return Ok(());
+                }
+            }
+            for sandwich in sandwiches {
+                if sender.send(sandwich).await.is_err() {
+                    eprintln!("sandwich channel closed, stopping the replay");
+                    return Ok(());
+                }
+            }
+            if let Some(db_sender) = &db_sender {
+                for (slot, status) in slot_statuses(&block) {
+                    if db_sender.send(DbMessage::Slot(slot, status)).await.is_err() {
+                        eprintln!("db channel closed, stopping the replay");
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,210 @@
+use std::{collections::HashMap, fmt::Display, time};
+
+use serde::Deserialize;
+
+use crate::storage::{SandwichStore, SlotStatus, StorageError};
+
+const Z: f64 = 3.89059188641; // p-value 0.0001
+
+pub fn p_conf_interval(n: f64, k: f64) -> (f64, f64) {
+    let p = k / n;
+    let a = (p + Z * Z / (2.0 * n)) / (1.0 + Z * Z / n);
+    let b = Z / (1.0 + Z * Z / n) * (p * (1.0 - p) / n + Z * Z / (4.0 * n * n)).sqrt();
+    (a - b, a + b)
+}
+
+pub fn count_conf_interval(mu: f64, stdev: f64, n: f64) -> (f64, f64) {
+    let a = mu - Z * stdev / n.sqrt();
+    let b = mu + Z * stdev / n.sqrt();
+    (a, b)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidatorInfo {
+    pub identity: String,
+    pub vote_pubkey: Option<String>,
+    pub name: Option<String>,
+}
+
+pub struct ReportEntry {
+    pub leader: String,
+    pub sc: f64,
+    pub sc_p: f64,
+    pub rsc: f64,
+    pub rsc_p: f64,
+    /// blocks produced by the leader in observed slots
+    pub slots: i32,
+    pub skipped: i32,
+    pub skip_rate: f64,
+}
+
+pub struct Report {
+    pub slot_range: (i64, i64),
+    pub entries: Vec<ReportEntry>,
+    pub mean: f64,
+    pub stdev: f64,
+    pub w_sc: f64,
+    pub w_sc_p: f64,
+    /// slots in the range we have observed a block in
+    pub observed_slots: u64,
+    pub skipped_slots: u64,
+}
+
+#[derive(Debug)]
+pub enum ReportError {
+    Storage(StorageError),
+    /// every score is relative to the observed slots, so there's nothing to report without any
+    NoObservedSlots((i64, i64)),
+}
+
+impl Display for ReportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Storage(e) => write!(f, "{e}"),
+            Self::NoObservedSlots(slot_range) => write!(f, "no observed slots in range {:?}", slot_range),
+        }
+    }
+}
+
+impl std::error::Error for ReportError {}
+
+impl From<StorageError> for ReportError {
+    fn from(e: StorageError) -> Self {
+        Self::Storage(e)
+    }
+}
+
+/// Sandwicher-colluder report
+/// The main metrics we're looking for here are sandwiches per slot (Sc) and proportion of slots with sandwiches (Sc_p),
+/// and our hypothesis is that colluders will have a higher value in both values, compared to the cluster average.
+/// Solana validators typically only receive transactions when it's close to their leader slot,
+/// and colluders relays these transactions to the sandwichers, who will sandwich the transactions where feasible and submit ASAP,
+/// or the tx may land on its own (without its slippage being artifically inflated!).
+/// Therefore, colluders are expected to have higher Sc and Sc_p values compared to non-colluders.
+/// Since txs may take a couple slots to land (sent to a colluder but landed after the colluder's leader slots), leaders
+/// of prior slots (`offset_range`) will also be credited for any given sandwich. Ideally, slots farther away should receive
+/// less credits, and the exact distribution should resemble that of the actual latency of sandwichable txs, but that's unimplemented for now.
+/// Only slots we observed a block in are considered, so neither downtime of the finder nor skipped slots deflate any leader's scores.
+/// Only sandwiches matched by `rule_set` are counted, as the same sandwich may be stored once per rule set,
+/// and only those with a confidence of at least `min_confidence`.
+pub fn generate_report(store: &mut dyn SandwichStore, slot_range: (i64, i64), rule_set: &str, min_confidence: f64) -> Result<Report, ReportError> {
+    let now = time::Instant::now();
+    let (start, end) = (slot_range.0 as u64, slot_range.1 as u64);
+    // observed slots in the range, which the mean and every score is relative to
+    let n = store.slot_count(start, end, SlotStatus::Observed)?;
+    if n == 0 {
+        return Err(ReportError::NoObservedSlots(slot_range));
+    }
+    let offset_range = vec![0.2, 1.0, 0.6, 0.4, 0.2];
+    // fetch leaders of observed slots within the concerned slot range to serve as the basis of normalisation,
+    // leaders are interned as there's one row per slot
+    let mut leaders: Vec<String> = Vec::new();
+    let mut leader_ids: HashMap<String, usize> = HashMap::new();
+    let mut observed_leaders: HashMap<u64, usize> = HashMap::new();
+    let mut leader_count: HashMap<String, u64> = HashMap::new();
+    store.for_each_slot_leader(start, end, SlotStatus::Observed, &mut |slot, leader| {
+        let id = *leader_ids.entry(leader.to_string()).or_insert_with(|| {
+            leaders.push(leader.to_string());
+            leaders.len() - 1
+        });
+        observed_leaders.insert(slot, id);
+        *leader_count.entry(leader.to_string()).or_insert(0) += 1;
+    })?;
+    let mut skip_count: HashMap<String, u64> = HashMap::new();
+    store.for_each_slot_leader(start, end, SlotStatus::Skipped, &mut |_, leader| {
+        *skip_count.entry(leader.to_string()).or_insert(0) += 1;
+    })?;
+    eprintln!("[+{:7}ms] Consolidated leader schedule", now.elapsed().as_millis());
+    // sandwiches up to the last offset after the range are credited to slots in the range
+    let sandwich_slots = store.sandwiches_per_slot(start, end + offset_range.len() as u64 - 1, rule_set, min_confidence)?;
+    eprintln!("[+{:7}ms] Fetched sandwiches", now.elapsed().as_millis());
+    let mut sx = 0.0;
+    let mut sxx = 0.0;
+    sandwich_slots.iter().filter(|(slot, _)| *slot <= end).for_each(|(_, count)| {
+        let x = *count as f64;
+        sx += x;
+        sxx += x * x;
+    });
+    // mean and sd of sandwiches per observed slot
+    let mean = sx / n as f64;
+    let stdev = (sxx / n as f64 - mean * mean).sqrt();
+    eprintln!("[+{:7}ms] Consolidated frequencies", now.elapsed().as_millis());
+    // raw score calculations (sandwiches in leader slot with offset to account for tx delay)
+    let mut scores: HashMap<String, f64> = HashMap::new();
+    let mut presence_scores: HashMap<String, f64> = HashMap::new();
+    let mut total_score = 0.0;
+    let mut total_presence_score = 0.0;
+    for i in 0..offset_range.len() {
+        for (slot, count) in sandwich_slots.iter() {
+            let Some(leader) = slot.checked_sub(i as u64).filter(|slot| *slot >= start && *slot <= end).and_then(|slot| observed_leaders.get(&slot)) else {
+                continue;
+            };
+            let leader = &leaders[*leader];
+            let count = *count as f64 * offset_range[i];
+            *scores.entry(leader.clone()).or_insert(0.0) += count;
+            total_score += count;
+            *presence_scores.entry(leader.clone()).or_insert(0.0) += offset_range[i];
+            total_presence_score += offset_range[i];
+        }
+        eprintln!("[+{:7}ms] Completed iteration {i}", now.elapsed().as_millis());
+    }
+    // normalise scores into an approximate measure of sandwiches per slot
+    let norm_factor = offset_range.iter().sum::<f64>();
+    let normalised_scores = scores.iter().map(|(k, v)| {
+        let count = leader_count.get(k).unwrap_or(&0);
+        (k.clone(), *v as f64 / *count as f64 / norm_factor)
+    }).collect::<HashMap<String, f64>>();
+    let presence_normalised_scores = presence_scores.iter().map(|(k, v)| {
+        let count = leader_count.get(k).unwrap_or(&0);
+        (k.clone(), *v as f64 / *count as f64 / norm_factor)
+    }).collect::<HashMap<String, f64>>();
+    let mut entries = normalised_scores.iter().map(|(k, v)| {
+        let slots = leader_count[k] as f64;
+        let skipped = *skip_count.get(k).unwrap_or(&0) as f64;
+        ReportEntry {
+            leader: k.clone(),
+            sc: *v,
+            sc_p: presence_normalised_scores[k],
+            rsc: v * slots,
+            rsc_p: presence_normalised_scores[k] * slots,
+            slots: slots as i32,
+            skipped: skipped as i32,
+            skip_rate: skipped / (slots + skipped),
+        }
+    }).collect::<Vec<_>>();
+    // and sort by presence, then frequency
+    entries.sort_by(|a, b| {
+        let a = (a.sc_p, a.sc);
+        let b = (b.sc_p, b.sc);
+        b.partial_cmp(&a).unwrap()
+    });
+    let w_sc_p = total_presence_score as f64 / n as f64 / norm_factor;
+    let w_sc = total_score as f64 / n as f64 / norm_factor;
+    Ok(Report {
+        slot_range,
+        entries,
+        mean,
+        stdev,
+        w_sc,
+        w_sc_p,
+        observed_slots: n,
+        skipped_slots: skip_count.values().sum(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{migrate::migrate, storage::SqliteStore};
+
+    #[test]
+    fn rejects_range_without_observed_slots() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        migrate(&mut store).unwrap();
+        store.write_slot_status(5, SlotStatus::Skipped).unwrap();
+        store.write_slot_status(20, SlotStatus::Observed).unwrap();
+        let report = generate_report(&mut store, (0, 10), "default", 0.0);
+        assert!(matches!(report, Err(ReportError::NoObservedSlots((0, 10)))));
+    }
+}
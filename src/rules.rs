@@ -0,0 +1,503 @@
+use std::{collections::HashSet, path::Path};
+
+use serde::Deserialize;
+
+use crate::decode::{config::load_file, Swap};
+
+const JUPITER_PROGRAM: &str = "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4";
+
+/// A single, configurable detection criterion.
+/// Structural criteria (inclusion order, direction, pair and pool grouping) are always applied by `find_sandwiches`.
+pub trait SandwichRule: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Whether the frontrun and the backrun can form a sandwich
+    fn check_legs(&self, _frontrun: &Swap, _backrun: &Swap) -> bool {
+        true
+    }
+
+    /// Whether a swap between the legs counts as a victim
+    fn check_victim(&self, _frontrun: &Swap, _victim: &Swap, _backrun: &Swap) -> bool {
+        true
+    }
+
+    /// Final check once the victims are known
+    fn check_victims(&self, _victims: &[Swap]) -> bool {
+        true
+    }
+}
+
+/// The backrun gets back at least what the frontrun paid and doesn't sell more than the frontrun bought (check #3).
+/// `tolerance` is the fraction either side may be off by, e.g. to account for fees.
+pub struct Profitability {
+    pub tolerance: f64,
+}
+
+impl SandwichRule for Profitability {
+    fn name(&self) -> &str {
+        "profitability"
+    }
+
+    fn check_legs(&self, frontrun: &Swap, backrun: &Swap) -> bool {
+        backrun.output_amount as f64 >= frontrun.input_amount as f64 * (1.0 - self.tolerance)
+            && backrun.input_amount as f64 <= frontrun.output_amount as f64 * (1.0 + self.tolerance)
+    }
+}
+
+/// The victims' signers differ from the legs' (check #5)
+pub struct DistinctVictimSigner;
+
+impl SandwichRule for DistinctVictimSigner {
+    fn name(&self) -> &str {
+        "distinct_victim_signer"
+    }
+
+    fn check_victim(&self, frontrun: &Swap, victim: &Swap, backrun: &Swap) -> bool {
+        victim.signer != frontrun.signer && victim.signer != backrun.signer
+    }
+}
+
+/// Both legs go through the same wrapper program, which isn't one of the excluded ones (check #6)
+pub struct SharedWrapper {
+    pub excluded: HashSet<String>,
+}
+
+impl SandwichRule for SharedWrapper {
+    fn name(&self) -> &str {
+        "shared_wrapper"
+    }
+
+    fn check_legs(&self, frontrun: &Swap, backrun: &Swap) -> bool {
+        match (&frontrun.outer_program, &backrun.outer_program) {
+            (Some(a), Some(b)) => a == b && !self.excluded.contains(a),
+            _ => false,
+        }
+    }
+}
+
+/// Whether the backrun may use a different pool of the pair than the frontrun, and if so whether both need the same signer (check #7)
+pub struct CrossAmm {
+    pub allowed: bool,
+    pub same_signer: bool,
+}
+
+impl SandwichRule for CrossAmm {
+    fn name(&self) -> &str {
+        "cross_amm"
+    }
+
+    fn check_legs(&self, frontrun: &Swap, backrun: &Swap) -> bool {
+        frontrun.amm == backrun.amm || (self.allowed && (!self.same_signer || frontrun.signer == backrun.signer))
+    }
+}
+
+/// Number of victims within bounds
+pub struct VictimCount {
+    pub min: usize,
+    pub max: Option<usize>,
+}
+
+impl SandwichRule for VictimCount {
+    fn name(&self) -> &str {
+        "victim_count"
+    }
+
+    fn check_victims(&self, victims: &[Swap]) -> bool {
+        victims.len() >= self.min && self.max.map_or(true, |max| victims.len() <= max)
+    }
+}
+
+/// A named, versioned set of rules, recorded on every sandwich it matched
+pub struct RuleSet {
+    pub name: String,
+    pub version: u32,
+    rules: Vec<Box<dyn SandwichRule>>,
+}
+
+impl RuleSet {
+    pub fn new(name: &str, version: u32) -> Self {
+        Self {
+            name: name.to_string(),
+            version,
+            rules: Vec::new(),
+        }
+    }
+
+    pub fn with_rule(mut self, rule: Box<dyn SandwichRule>) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn rules(&self) -> &[Box<dyn SandwichRule>] {
+        &self.rules
+    }
+
+    pub fn check_legs(&self, frontrun: &Swap, backrun: &Swap) -> bool {
+        self.rules.iter().all(|rule| rule.check_legs(frontrun, backrun))
+    }
+
+    pub fn check_victim(&self, frontrun: &Swap, victim: &Swap, backrun: &Swap) -> bool {
+        self.rules.iter().all(|rule| rule.check_victim(frontrun, victim, backrun))
+    }
+
+    pub fn check_victims(&self, victims: &[Swap]) -> bool {
+        self.rules.iter().all(|rule| rule.check_victims(victims))
+    }
+}
+
+impl Default for RuleSet {
+    /// The criteria described in the README
+    fn default() -> Self {
+        RuleSetDefinition::default().build()
+    }
+}
+
+/// Rule sets loaded from a TOML or JSON file, e.g.
+/// ```toml
+/// [[rule_set]]
+/// name = "loose"
+/// version = 1
+///
+/// [rule_set.profitability]
+/// tolerance = 0.01
+///
+/// [rule_set.shared_wrapper]
+/// enabled = false
+/// ```
+/// Omitted rules keep their default settings. Every rule set is run on every block, each sandwich records the one it matched.
+#[derive(Deserialize)]
+pub struct RulesConfig {
+    #[serde(default)]
+    pub rule_set: Vec<RuleSetDefinition>,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct RuleSetDefinition {
+    pub name: String,
+    pub version: u32,
+    pub profitability: ProfitabilityDefinition,
+    pub distinct_victim_signer: ToggleDefinition,
+    pub shared_wrapper: SharedWrapperDefinition,
+    pub cross_amm: CrossAmmDefinition,
+    pub victim_count: VictimCountDefinition,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ToggleDefinition {
+    pub enabled: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ProfitabilityDefinition {
+    pub enabled: bool,
+    pub tolerance: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct SharedWrapperDefinition {
+    pub enabled: bool,
+    pub excluded: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct CrossAmmDefinition {
+    pub enabled: bool,
+    pub same_signer: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct VictimCountDefinition {
+    pub min: usize,
+    pub max: Option<usize>,
+}
+
+impl Default for RuleSetDefinition {
+    fn default() -> Self {
+        Self {
+            name: "default".to_string(),
+            version: 1,
+            profitability: ProfitabilityDefinition::default(),
+            distinct_victim_signer: ToggleDefinition::default(),
+            shared_wrapper: SharedWrapperDefinition::default(),
+            cross_amm: CrossAmmDefinition::default(),
+            victim_count: VictimCountDefinition::default(),
+        }
+    }
+}
+
+impl Default for ToggleDefinition {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+impl Default for ProfitabilityDefinition {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            tolerance: 0.0,
+        }
+    }
+}
+
+impl Default for SharedWrapperDefinition {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            // jupiter is an aggregator, not a sandwicher
+            excluded: vec![JUPITER_PROGRAM.to_string()],
+        }
+    }
+}
+
+impl Default for CrossAmmDefinition {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            same_signer: true,
+        }
+    }
+}
+
+impl Default for VictimCountDefinition {
+    fn default() -> Self {
+        Self { min: 1, max: None }
+    }
+}
+
+impl RuleSetDefinition {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err("rule set name must not be empty".to_string());
+        }
+        if !(0.0..1.0).contains(&self.profitability.tolerance) {
+            return Err(format!("{}: profitability tolerance must be within [0, 1)", self.name));
+        }
+        if self.victim_count.min == 0 {
+            return Err(format!("{}: victim_count min must be at least 1", self.name));
+        }
+        if self.victim_count.max.is_some_and(|max| max < self.victim_count.min) {
+            return Err(format!("{}: victim_count max is less than min", self.name));
+        }
+        Ok(())
+    }
+
+    pub fn build(&self) -> RuleSet {
+        let mut rule_set = RuleSet::new(&self.name, self.version);
+        if self.profitability.enabled {
+            rule_set = rule_set.with_rule(Box::new(Profitability { tolerance: self.profitability.tolerance }));
+        }
+        if self.distinct_victim_signer.enabled {
+            rule_set = rule_set.with_rule(Box::new(DistinctVictimSigner));
+        }
+        if self.shared_wrapper.enabled {
+            rule_set = rule_set.with_rule(Box::new(SharedWrapper { excluded: self.shared_wrapper.excluded.iter().cloned().collect() }));
+        }
+        rule_set = rule_set.with_rule(Box::new(CrossAmm { allowed: self.cross_amm.enabled, same_signer: self.cross_amm.same_signer }));
+        rule_set.with_rule(Box::new(VictimCount { min: self.victim_count.min, max: self.victim_count.max }))
+    }
+}
+
+impl RulesConfig {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        load_file(path)
+    }
+
+    /// The configured rule sets, or the default one if none are configured
+    pub fn build(&self) -> Result<Vec<RuleSet>, String> {
+        if self.rule_set.is_empty() {
+            return Ok(vec![RuleSet::default()]);
+        }
+        let mut names = HashSet::new();
+        self.rule_set.iter().map(|definition| {
+            if !names.insert(definition.name.as_str()) {
+                return Err(format!("{}: duplicate rule set name", definition.name));
+            }
+            definition.validate()?;
+            Ok(definition.build())
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode::fixtures::{swap, MINT_A, MINT_B}, detect::find_sandwiches};
+
+    const WRAPPER: &str = "wrapper";
+
+    fn leg(signer: &str, amm: &str, outer_program: Option<&str>, input_amount: u64, output_amount: u64) -> Swap {
+        Swap {
+            signer: signer.to_string(),
+            amm: amm.to_string(),
+            outer_program: outer_program.map(str::to_string),
+            ..swap(MINT_A, MINT_B, input_amount, output_amount)
+        }
+    }
+
+    fn definition(name: &str) -> RuleSetDefinition {
+        RuleSetDefinition {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn profitability() {
+        let rule = Profitability { tolerance: 0.0 };
+        let frontrun = leg("a", "amm", None, 100, 50);
+        assert!(rule.check_legs(&frontrun, &leg("a", "amm", None, 50, 100)));
+        // got back less than paid, or sold more than bought
+        assert!(!rule.check_legs(&frontrun, &leg("a", "amm", None, 50, 99)));
+        assert!(!rule.check_legs(&frontrun, &leg("a", "amm", None, 51, 100)));
+        let rule = Profitability { tolerance: 0.1 };
+        assert!(rule.check_legs(&frontrun, &leg("a", "amm", None, 55, 90)));
+        assert!(!rule.check_legs(&frontrun, &leg("a", "amm", None, 56, 90)));
+        assert!(!rule.check_legs(&frontrun, &leg("a", "amm", None, 55, 89)));
+    }
+
+    #[test]
+    fn distinct_victim_signer() {
+        let (frontrun, backrun) = (leg("a", "amm", None, 100, 50), leg("b", "amm", None, 50, 100));
+        assert!(DistinctVictimSigner.check_victim(&frontrun, &leg("v", "amm", None, 10, 5), &backrun));
+        assert!(!DistinctVictimSigner.check_victim(&frontrun, &leg("a", "amm", None, 10, 5), &backrun));
+        assert!(!DistinctVictimSigner.check_victim(&frontrun, &leg("b", "amm", None, 10, 5), &backrun));
+    }
+
+    #[test]
+    fn shared_wrapper() {
+        let rule = SharedWrapperDefinition::default();
+        let rule = SharedWrapper { excluded: rule.excluded.into_iter().collect() };
+        let wrapped = leg("a", "amm", Some(WRAPPER), 100, 50);
+        assert!(rule.check_legs(&wrapped, &wrapped));
+        assert!(!rule.check_legs(&wrapped, &leg("a", "amm", Some("other"), 50, 100)));
+        assert!(!rule.check_legs(&wrapped, &leg("a", "amm", None, 50, 100)));
+        assert!(!rule.check_legs(&leg("a", "amm", None, 100, 50), &leg("a", "amm", None, 50, 100)));
+        let jupiter = leg("a", "amm", Some(JUPITER_PROGRAM), 100, 50);
+        assert!(!rule.check_legs(&jupiter, &jupiter));
+    }
+
+    #[test]
+    fn cross_amm() {
+        let frontrun = leg("a", "amm", None, 100, 50);
+        let (same_amm, same_signer, neither) = (leg("b", "amm", None, 50, 100), leg("a", "other", None, 50, 100), leg("b", "other", None, 50, 100));
+        let rule = CrossAmm { allowed: true, same_signer: true };
+        assert!(rule.check_legs(&frontrun, &same_amm));
+        assert!(rule.check_legs(&frontrun, &same_signer));
+        assert!(!rule.check_legs(&frontrun, &neither));
+        let rule = CrossAmm { allowed: true, same_signer: false };
+        assert!(rule.check_legs(&frontrun, &neither));
+        let rule = CrossAmm { allowed: false, same_signer: true };
+        assert!(rule.check_legs(&frontrun, &same_amm));
+        assert!(!rule.check_legs(&frontrun, &same_signer));
+    }
+
+    #[test]
+    fn victim_count() {
+        let victims = vec![leg("v", "amm", None, 10, 5); 3];
+        assert!(VictimCount { min: 1, max: None }.check_victims(&victims));
+        assert!(VictimCount { min: 3, max: Some(3) }.check_victims(&victims));
+        assert!(!VictimCount { min: 4, max: None }.check_victims(&victims));
+        assert!(!VictimCount { min: 1, max: Some(2) }.check_victims(&victims));
+        assert!(!VictimCount { min: 1, max: None }.check_victims(&[]));
+    }
+
+    #[test]
+    fn validates_definitions() {
+        assert!(definition("default").validate().is_ok());
+        assert!(definition("").validate().is_err());
+        for (tolerance, valid) in [(0.0, true), (0.99, true), (-0.01, false), (1.0, false), (f64::NAN, false)] {
+            let mut rule_set = definition("tolerance");
+            rule_set.profitability.tolerance = tolerance;
+            assert_eq!(rule_set.validate().is_ok(), valid, "tolerance {tolerance}");
+        }
+        for (min, max, valid) in [(1, None, true), (2, Some(2), true), (0, None, false), (3, Some(2), false)] {
+            let mut rule_set = definition("victim_count");
+            rule_set.victim_count = VictimCountDefinition { min, max };
+            assert_eq!(rule_set.validate().is_ok(), valid, "victim_count {min} {max:?}");
+        }
+    }
+
+    #[test]
+    fn builds_rule_sets() {
+        let rule_sets = RulesConfig { rule_set: vec![] }.build().unwrap();
+        assert_eq!(rule_sets.iter().map(|rules| (rules.name.as_str(), rules.version)).collect::<Vec<_>>(), vec![("default", 1)]);
+        let config: RulesConfig = toml::from_str("[[rule_set]]\nname = \"loose\"\nversion = 2\n\n[rule_set.shared_wrapper]\nenabled = false\n\n[[rule_set]]\nname = \"strict\"\n").unwrap();
+        let rule_sets = config.build().unwrap();
+        assert_eq!(rule_sets.iter().map(|rules| (rules.name.as_str(), rules.version)).collect::<Vec<_>>(), vec![("loose", 2), ("strict", 1)]);
+        // omitted rules keep their defaults
+        let names = |rules: &RuleSet| rules.rules().iter().map(|rule| rule.name().to_string()).collect::<Vec<_>>();
+        assert_eq!(names(&rule_sets[0]), vec!["profitability", "distinct_victim_signer", "cross_amm", "victim_count"]);
+        assert_eq!(names(&rule_sets[1]), vec!["profitability", "distinct_victim_signer", "shared_wrapper", "cross_amm", "victim_count"]);
+        assert!(RulesConfig { rule_set: vec![definition("a"), definition("b"), definition("a")] }.build().is_err());
+        assert!(RulesConfig { rule_set: vec![definition("a"), definition("")] }.build().is_err());
+    }
+
+    /// The checks `find_sandwiches` hard-coded before rule sets were configurable
+    fn hard_coded_sandwiches(in_trades: &[&Swap], out_trades: &[&Swap]) -> Vec<(String, String, Vec<String>)> {
+        let mut sandwiches = Vec::new();
+        for i in 0..in_trades.len() {
+            for j in (0..out_trades.len()).rev() {
+                let (in_trade, out_trade) = (in_trades[i], out_trades[j]);
+                if out_trade.order <= in_trade.order {
+                    break;
+                }
+                if out_trade.output_amount < in_trade.input_amount || out_trade.input_amount > in_trade.output_amount {
+                    continue;
+                }
+                if in_trade.outer_program != out_trade.outer_program || in_trade.outer_program.is_none() || out_trade.outer_program.is_none() {
+                    continue;
+                }
+                if in_trade.outer_program == Some(JUPITER_PROGRAM.to_string()) {
+                    continue;
+                }
+                if out_trade.amm != in_trade.amm && out_trade.signer != in_trade.signer {
+                    continue;
+                }
+                let mut victims = Vec::new();
+                for victim in in_trades[i + 1..].iter() {
+                    if victim.order >= out_trade.order {
+                        break;
+                    }
+                    if victim.amm != in_trade.amm || victim.signer == in_trade.signer || victim.signer == out_trade.signer {
+                        continue;
+                    }
+                    victims.push(victim.sig.clone());
+                }
+                if !victims.is_empty() {
+                    sandwiches.push((in_trade.sig.clone(), out_trade.sig.clone(), victims));
+                }
+            }
+        }
+        sandwiches
+    }
+
+    #[test]
+    fn default_rule_set_matches_hard_coded_checks() {
+        // interleaved legs cycling through amounts, pools, signers and wrappers so that each check rejects some of them
+        let outer_programs = [Some(WRAPPER), None, Some(JUPITER_PROGRAM), Some("wrapper2")];
+        let in_trades = (0..12).map(|i| Swap {
+            order: 2 * i as u64,
+            sig: format!("in{i}"),
+            ..leg(["s1", "s2", "v1"][i % 3], ["a", "a", "b"][i % 3], outer_programs[i % 4], [100, 150, 200][i % 3], [200, 150][i % 2])
+        }).collect::<Vec<_>>();
+        let outer_programs = [Some(WRAPPER), Some(WRAPPER), None, Some(JUPITER_PROGRAM)];
+        let out_trades = (0..12).map(|j| Swap {
+            order: 2 * j as u64 + 1,
+            sig: format!("out{j}"),
+            ..leg(["s1", "s3"][j % 2], ["a", "b"][(j / 2) % 2], outer_programs[j % 4], [150, 200][j % 2], [100, 150, 200][j % 3])
+        }).collect::<Vec<_>>();
+        let (in_trades, out_trades) = (in_trades.iter().collect::<Vec<_>>(), out_trades.iter().collect::<Vec<_>>());
+        let expected = hard_coded_sandwiches(&in_trades, &out_trades);
+        assert_eq!(expected.len(), 5);
+        let found = find_sandwiches(&in_trades, &out_trades, 0, 0, &RuleSet::default());
+        let found = found.into_iter().map(|sandwich| (sandwich.frontrun.sig, sandwich.backrun.sig, sandwich.victim.into_iter().map(|victim| victim.sig).collect::<Vec<_>>())).collect::<Vec<_>>();
+        assert_eq!(found, expected);
+    }
+}
@@ -0,0 +1,177 @@
+use std::{fs::{self, File, OpenOptions}, io::{self, BufRead, BufReader, BufWriter, Write}, path::{Path, PathBuf}};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{decode::Swap, detect::{Profit, Sandwich}, storage::{DbBlock, DbMessage, SlotStatus}};
+
+/// Append-only file of the db messages that couldn't be written, one JSON message per line,
+/// replayed in order once the db is reachable again
+pub struct Spool {
+    path: PathBuf,
+}
+
+/// `DbMessage` as written to the spool
+#[derive(Serialize, Deserialize)]
+enum SpooledMessage {
+    Block(DbBlock, Vec<SpooledSandwich>),
+    Slot(u64, SlotStatus),
+}
+
+/// What's stored of a sandwich, as its own serialization is the api's
+#[derive(Serialize, Deserialize)]
+struct SpooledSandwich {
+    slot: u64,
+    frontrun: Swap,
+    victim: Vec<Swap>,
+    backrun: Swap,
+    ts: i64,
+    profit: Profit,
+    loss_sol: Option<f64>,
+    profit_sol: Option<f64>,
+    rule_set: String,
+    rule_version: u32,
+    confidence: f64,
+    sandwicher_id: Option<u64>,
+    sandwicher_members: Vec<String>,
+    #[serde(default)]
+    merged_sandwicher_ids: Vec<u64>,
+    frontrun_tip: u64,
+    backrun_tip: u64,
+    bundle_id: Option<String>,
+    bundled: Option<bool>,
+}
+
+impl From<&Sandwich> for SpooledSandwich {
+    fn from(sandwich: &Sandwich) -> Self {
+        Self {
+            slot: sandwich.slot,
+            frontrun: sandwich.frontrun.clone(),
+            victim: sandwich.victim.clone(),
+            backrun: sandwich.backrun.clone(),
+            ts: sandwich.ts,
+            profit: sandwich.profit.clone(),
+            loss_sol: sandwich.loss_sol,
+            profit_sol: sandwich.profit_sol,
+            rule_set: sandwich.rule_set.clone(),
+            rule_version: sandwich.rule_version,
+            confidence: sandwich.confidence,
+            sandwicher_id: sandwich.sandwicher_id,
+            sandwicher_members: sandwich.sandwicher_members.clone(),
+            merged_sandwicher_ids: sandwich.merged_sandwicher_ids.clone(),
+            frontrun_tip: sandwich.frontrun_tip,
+            backrun_tip: sandwich.backrun_tip,
+            bundle_id: sandwich.bundle_id.clone(),
+            bundled: sandwich.bundled,
+        }
+    }
+}
+
+impl From<SpooledSandwich> for Sandwich {
+    fn from(sandwich: SpooledSandwich) -> Self {
+        Self {
+            slot: sandwich.slot,
+            frontrun: sandwich.frontrun,
+            victim: sandwich.victim,
+            backrun: sandwich.backrun,
+            ts: sandwich.ts,
+            profit: sandwich.profit,
+            loss_sol: sandwich.loss_sol,
+            profit_sol: sandwich.profit_sol,
+            rule_set: sandwich.rule_set,
+            rule_version: sandwich.rule_version,
+            confidence: sandwich.confidence,
+            // the signals aren't stored
+            signals: None,
+            sandwicher_id: sandwich.sandwicher_id,
+            sandwicher_members: sandwich.sandwicher_members,
+            merged_sandwicher_ids: sandwich.merged_sandwicher_ids,
+            frontrun_tip: sandwich.frontrun_tip,
+            backrun_tip: sandwich.backrun_tip,
+            bundle_id: sandwich.bundle_id,
+            bundled: sandwich.bundled,
+        }
+    }
+}
+
+impl From<&DbMessage> for SpooledMessage {
+    fn from(msg: &DbMessage) -> Self {
+        match msg {
+            DbMessage::Block(block, sandwiches) => Self::Block(block.clone(), sandwiches.iter().map(SpooledSandwich::from).collect()),
+            DbMessage::Slot(slot, status) => Self::Slot(*slot, *status),
+        }
+    }
+}
+
+impl From<SpooledMessage> for DbMessage {
+    fn from(msg: SpooledMessage) -> Self {
+        match msg {
+            SpooledMessage::Block(block, sandwiches) => Self::Block(block, sandwiches.into_iter().map(Sandwich::from).collect()),
+            SpooledMessage::Slot(slot, status) => Self::Slot(slot, status),
+        }
+    }
+}
+
+impl Spool {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether there are messages waiting to be replayed
+    pub fn is_empty(&self) -> bool {
+        fs::metadata(&self.path).map_or(true, |metadata| metadata.len() == 0)
+    }
+
+    pub fn append(&self, msg: &DbMessage) -> io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        let mut line = serde_json::to_string(&SpooledMessage::from(msg))?;
+        line.push('\n');
+        file.write_all(line.as_bytes())?;
+        file.sync_data()
+    }
+
+    /// The spooled messages in the order they were appended, lines that don't parse (e.g. cut off by a crash) are skipped
+    pub fn read(&self) -> io::Result<Vec<DbMessage>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut messages = Vec::new();
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<SpooledMessage>(&line) {
+                Ok(msg) => messages.push(msg.into()),
+                Err(e) => println!("skipping line {} of {}: {e}", i + 1, self.path.display()),
+            }
+        }
+        Ok(messages)
+    }
+
+    /// Replaces the spooled messages with `messages`, i.e. those still not written after a replay
+    pub fn replace(&self, messages: &[DbMessage]) -> io::Result<()> {
+        if messages.is_empty() {
+            return match fs::remove_file(&self.path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+        // write to a temp file first so that a crash doesn't lose the spool
+        let tmp_path = self.path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        for msg in messages {
+            serde_json::to_writer(&mut writer, &SpooledMessage::from(msg))?;
+            writer.write_all(b"\n")?;
+        }
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&tmp_path, &self.path)
+    }
+}
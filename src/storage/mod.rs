@@ -0,0 +1,359 @@
+use std::{collections::{BTreeMap, HashMap, HashSet}, env, fmt::Display, path::Path, time::{Duration, Instant}};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::{decode::Swap, detect::Sandwich, migrate::Migration, spool::Spool};
+
+mod mysql;
+mod postgres;
+mod sqlite;
+
+pub use self::{mysql::MysqlStore, postgres::PostgresStore, sqlite::SqliteStore};
+
+/// Attempts at writing a message before it's spooled
+const WRITE_ATTEMPTS: u32 = 3;
+/// Delay before the first retry, doubled on each retry
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+/// How often to check whether the db is back while spooling
+const RECOVERY_INTERVAL: Duration = Duration::from_secs(10);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Transaction ids are cached for this many slots, sandwiches sharing a transaction are found close together
+const CACHED_SLOTS: u64 = 1000;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DbBlock {
+    pub slot: u64,
+    pub ts: i64,
+    pub tx_count: usize,
+}
+
+#[derive(Clone)]
+pub enum DbMessage {
+    /// A processed block with the sandwiches found in it, written in a single db transaction
+    Block(DbBlock, Vec<Sandwich>),
+    Slot(u64, SlotStatus),
+}
+
+/// What we know about a slot, slots without a status were never observed by us
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SlotStatus {
+    /// The block was received and processed
+    Observed,
+    /// The leader didn't produce a block
+    Skipped,
+    /// We tried but couldn't get the block
+    Missed,
+}
+
+impl SlotStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SlotStatus::Observed => "OBSERVED",
+            SlotStatus::Skipped => "SKIPPED",
+            SlotStatus::Missed => "MISSED",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum SwapType {
+    Frontrun,
+    Victim,
+    Backrun,
+}
+
+impl SwapType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SwapType::Frontrun => "FRONTRUN",
+            SwapType::Victim => "VICTIM",
+            SwapType::Backrun => "BACKRUN",
+        }
+    }
+}
+
+/// The database a store talks to, each has its own migrations, see `migrate::Migration`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    Mysql,
+    Postgres,
+    Sqlite,
+}
+
+/// Why a message couldn't be written
+#[derive(Debug)]
+pub enum StorageError {
+    Mysql(::mysql::Error),
+    Postgres(::postgres::Error),
+    Sqlite(rusqlite::Error),
+    /// neither DATABASE_URL nor MYSQL is set
+    MissingUrl,
+    /// the connection url has a scheme without a backend
+    UnsupportedUrl(String),
+    /// a swap's transaction wasn't found after inserting it
+    MissingTxId(String),
+    /// the id of an upserted sandwich wasn't returned
+    MissingSandwichId,
+}
+
+impl StorageError {
+    /// Whether the write may succeed if retried later, e.g. the connection dropped, as opposed to the message being rejected
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Mysql(::mysql::Error::IoError(_) | ::mysql::Error::DriverError(_)) => true,
+            // lock wait timeout, deadlock
+            Self::Mysql(::mysql::Error::MySqlError(e)) => e.code == 1205 || e.code == 1213,
+            Self::Postgres(e) => {
+                let conflict = e.code().map_or(false, |code| *code == ::postgres::error::SqlState::T_R_SERIALIZATION_FAILURE || *code == ::postgres::error::SqlState::T_R_DEADLOCK_DETECTED);
+                let io = std::error::Error::source(e).map_or(false, |source| source.is::<std::io::Error>());
+                e.is_closed() || conflict || io
+            }
+            Self::Sqlite(rusqlite::Error::SqliteFailure(e, _)) => matches!(e.code, rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked),
+            _ => false,
+        }
+    }
+}
+
+impl Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Mysql(e) => write!(f, "mysql error: {e}"),
+            Self::Postgres(e) => write!(f, "postgres error: {e}"),
+            Self::Sqlite(e) => write!(f, "sqlite error: {e}"),
+            Self::MissingUrl => write!(f, "DATABASE_URL is not set"),
+            Self::UnsupportedUrl(url) => write!(f, "unsupported db url {url}, expected mysql://, postgres:// or sqlite://"),
+            Self::MissingTxId(sig) => write!(f, "no id for transaction {sig}"),
+            Self::MissingSandwichId => write!(f, "no id for sandwich"),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<::mysql::Error> for StorageError {
+    fn from(e: ::mysql::Error) -> Self {
+        Self::Mysql(e)
+    }
+}
+
+impl From<::postgres::Error> for StorageError {
+    fn from(e: ::postgres::Error) -> Self {
+        Self::Postgres(e)
+    }
+}
+
+impl From<rusqlite::Error> for StorageError {
+    fn from(e: rusqlite::Error) -> Self {
+        Self::Sqlite(e)
+    }
+}
+
+/// Where sandwiches and everything the report needs are kept.
+/// Stores are blocking, so async code should only use them through `spawn_blocking` or `block_in_place`.
+pub trait SandwichStore: Send {
+    fn backend(&self) -> Backend;
+
+    /// Writes the block with its sandwiches, their swaps and txs in a db transaction, which is rolled back on error.
+    /// Everything is upserted so that reprocessing a block (e.g. replays, backfills, spool replays) doesn't duplicate rows,
+    /// and the swaps of sandwiches written before are replaced.
+    fn write_block(&mut self, block: &DbBlock, sandwiches: &[Sandwich]) -> Result<(), StorageError>;
+
+    /// A missed slot may be filled in later, but doesn't overwrite what we already know
+    fn write_slot_status(&mut self, slot: u64, status: SlotStatus) -> Result<(), StorageError>;
+
+    /// Slots in `[start, end]` that have already been observed or are known to be skipped
+    fn observed_slots(&mut self, start: u64, end: u64) -> Result<HashSet<u64>, StorageError>;
+
+    /// Latest slot that was observed or is known to be skipped, where the finder picks up after a restart
+    fn last_observed_slot(&mut self) -> Result<Option<u64>, StorageError>;
+
+    /// (member, sandwicher id) pairs recorded so far, see `cluster::SandwicherClusters::with_members`
+    fn sandwicher_members(&mut self) -> Result<Vec<(String, u64)>, StorageError>;
+
+    fn insert_leader_schedule(&mut self, leader_schedule: &HashMap<u64, &String>) -> Result<(), StorageError>;
+
+    /// Calls `f` with the slot and leader of every slot in `[start, end]` with the status, rows are streamed as there may be millions
+    fn for_each_slot_leader(&mut self, start: u64, end: u64, status: SlotStatus, f: &mut dyn FnMut(u64, &str)) -> Result<(), StorageError>;
+
+    /// Number of slots in `[start, end]` with the status
+    fn slot_count(&mut self, start: u64, end: u64, status: SlotStatus) -> Result<u64, StorageError>;
+
+    /// (slot, count) of the sandwiches of the rule set with at least the confidence, by the slot of their frontrun
+    fn sandwiches_per_slot(&mut self, start: u64, end: u64, rule_set: &str, min_confidence: f64) -> Result<Vec<(u64, u64)>, StorageError>;
+
+    /// Version of the schema in the db, 0 if it was never migrated
+    fn schema_version(&mut self) -> Result<u32, StorageError>;
+
+    /// Runs the migration's statements for this backend and records it
+    fn apply_migration(&mut self, migration: &Migration) -> Result<(), StorageError>;
+}
+
+/// Opens the store at `DATABASE_URL` (or `MYSQL`), the backend is picked by the scheme of the url:
+/// `mysql://`, `postgres://`/`postgresql://`, or `sqlite://<path>`/`sqlite::memory:`.
+/// Mysql and postgres only connect when needed, so this doesn't fail if the db is down.
+pub fn open_store() -> Result<Box<dyn SandwichStore>, StorageError> {
+    let url = env::var("DATABASE_URL").or_else(|_| env::var("MYSQL")).map_err(|_| StorageError::MissingUrl)?;
+    if url.starts_with("mysql://") {
+        Ok(Box::new(MysqlStore::new(&url)?))
+    } else if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+        Ok(Box::new(PostgresStore::new(&url)?))
+    } else if url == "sqlite::memory:" {
+        Ok(Box::new(SqliteStore::in_memory()?))
+    } else if let Some(path) = url.strip_prefix("sqlite://") {
+        Ok(Box::new(SqliteStore::open(Path::new(path))?))
+    } else {
+        Err(StorageError::UnsupportedUrl(url))
+    }
+}
+
+/// The swaps of a sandwich in order, with their role
+fn sandwich_swaps(sandwich: &Sandwich) -> Vec<(&Swap, SwapType)> {
+    let mut swaps = Vec::new();
+    swaps.push((&sandwich.frontrun, SwapType::Frontrun));
+    swaps.extend(sandwich.victim.iter().map(|x| (x, SwapType::Victim)));
+    swaps.push((&sandwich.backrun, SwapType::Backrun));
+    swaps
+}
+
+/// Db ids of the transactions written in the last `CACHED_SLOTS` slots
+#[derive(Default)]
+struct TxIdCache {
+    ids: HashMap<String, u64>,
+    by_slot: BTreeMap<u64, Vec<String>>,
+}
+
+impl TxIdCache {
+    fn get(&self, sig: &str) -> Option<u64> {
+        self.ids.get(sig).copied()
+    }
+
+    fn insert(&mut self, sig: String, slot: u64, id: u64) {
+        if self.ids.insert(sig.clone(), id).is_none() {
+            self.by_slot.entry(slot).or_default().push(sig);
+        }
+    }
+
+    /// Drops the transactions more than `CACHED_SLOTS` slots older than the newest one
+    fn evict(&mut self) {
+        let Some(newest) = self.by_slot.keys().next_back().copied() else {
+            return;
+        };
+        let kept = self.by_slot.split_off(&newest.saturating_sub(CACHED_SLOTS));
+        for sig in std::mem::replace(&mut self.by_slot, kept).into_values().flatten() {
+            self.ids.remove(&sig);
+        }
+    }
+
+    /// Caches the ids of a committed block's new txs
+    fn extend(&mut self, new_txs: &HashMap<&str, &Swap>, new_tx_ids: HashMap<String, u64>) {
+        for (sig, id) in new_tx_ids {
+            if let Some(swap) = new_txs.get(sig.as_str()) {
+                self.insert(sig, swap.slot, id);
+            }
+        }
+        self.evict();
+    }
+}
+
+struct DbWriter {
+    store: Box<dyn SandwichStore>,
+}
+
+impl DbWriter {
+    fn write(&mut self, msg: &DbMessage) -> Result<(), StorageError> {
+        match msg {
+            DbMessage::Block(block, sandwiches) => self.store.write_block(block, sandwiches),
+            DbMessage::Slot(slot, status) => self.store.write_slot_status(*slot, *status),
+        }
+    }
+
+    /// Retries transient failures with an exponential backoff
+    fn write_with_retries(&mut self, msg: &DbMessage) -> Result<(), StorageError> {
+        let mut delay = RETRY_DELAY;
+        let mut attempt = 1;
+        loop {
+            match self.write(msg) {
+                Err(e) if e.is_transient() && attempt < WRITE_ATTEMPTS => {
+                    println!("db write failed (attempt {attempt}/{WRITE_ATTEMPTS}): {e}");
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Writes the spooled messages in order, returns whether the spool was emptied.
+    /// Messages rejected by the db are dropped, on a transient failure the remaining ones are kept for later.
+    fn replay(&mut self, spool: &Spool) -> bool {
+        let messages = match spool.read() {
+            Ok(messages) => messages,
+            Err(e) => {
+                println!("unable to read the spool {}: {e}", spool.path().display());
+                return false;
+            }
+        };
+        let total = messages.len();
+        let mut written = 0;
+        for (i, msg) in messages.iter().enumerate() {
+            match self.write_with_retries(msg) {
+                Ok(()) => written += 1,
+                Err(e) if e.is_transient() => {
+                    println!("spool replay interrupted after {written}/{total} messages: {e}");
+                    if let Err(e) = spool.replace(&messages[i..]) {
+                        println!("unable to rewrite the spool {}: {e}", spool.path().display());
+                    }
+                    return false;
+                }
+                Err(e) => println!("dropping spooled message: {e}"),
+            }
+        }
+        if let Err(e) = spool.replace(&[]) {
+            println!("unable to clear the spool {}: {e}", spool.path().display());
+            return false;
+        }
+        println!("replayed {written}/{total} spooled messages");
+        true
+    }
+}
+
+/// Writes the messages to the store, blocking, so it should be run with `spawn_blocking`. Messages that still can't be
+/// written after retrying are appended to the spool at `DB_SPOOL` along with every message after them to keep their order,
+/// until the db is back and the spool has been replayed. Messages rejected by the db are logged and dropped.
+pub fn store_to_db(store: Box<dyn SandwichStore>, mut receiver: mpsc::Receiver<DbMessage>) {
+    let spool = Spool::new(Path::new(&env::var("DB_SPOOL").unwrap_or_else(|_| "db_spool.jsonl".to_string())));
+    let mut writer = DbWriter { store };
+    // leftovers from a previous run
+    let mut spooling = !spool.is_empty() && !writer.replay(&spool);
+    let mut last_recovery = Instant::now();
+    while let Some(msg) = receiver.blocking_recv() {
+        // only check every so often so that spooling keeps up with the finder while the db is down
+        if spooling && last_recovery.elapsed() >= RECOVERY_INTERVAL {
+            last_recovery = Instant::now();
+            spooling = !writer.replay(&spool);
+        }
+        if !spooling {
+            match writer.write_with_retries(&msg) {
+                Ok(()) => continue,
+                Err(e) if e.is_transient() => {
+                    println!("db unavailable, spooling to {}: {e}", spool.path().display());
+                    spooling = true;
+                    last_recovery = Instant::now();
+                }
+                Err(e) => {
+                    println!("unable to store message: {e}");
+                    continue;
+                }
+            }
+        }
+        if let Err(e) = spool.append(&msg) {
+            println!("unable to spool message, it's lost: {e}");
+        }
+    }
+    // flush what's left before exiting, e.g. at the end of a backfill
+    if spooling && !writer.replay(&spool) {
+        println!("messages left in the spool {}, they'll be written on the next run", spool.path().display());
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::{HashMap, HashSet};
+
+use mysql::{prelude::Queryable, Opts, OptsBuilder, Pool, PoolConstraints, PoolOpts, TxOpts, Value};
+
+use crate::{decode::Swap, detect::Sandwich, migrate::{statements, Migration}};
+
+use super::{sandwich_swaps, Backend, DbBlock, SandwichStore, SlotStatus, StorageError, SwapType, TxIdCache, CONNECT_TIMEOUT};
+
+const POOL_SIZE: usize = 4;
+
+const UPSERT_BLOCK: &str = "insert into block (slot, timestamp, tx_count) values (?, ?, ?) on duplicate key update timestamp = values(timestamp), tx_count = values(tx_count)";
+const UPSERT_TX: &str = "insert into transaction (tx_hash, signer, slot, order_in_block) values (?, ?, ?, ?) on duplicate key update signer = values(signer), slot = values(slot), order_in_block = values(order_in_block)";
+const INSERT_SLOT: &str = "insert into slot_status (slot, status) values (?, ?) on duplicate key update status = if(values(status) = 'MISSED', status, values(status))";
+const INSERT_MEMBER: &str = "insert ignore into sandwicher_member (member, sandwicher_id) values (?, ?)";
+// sandwiches are keyed by slot, frontrun and backrun (per rule set), last_insert_id(id) makes the id of an existing row available
+const UPSERT_SANDWICH: &str = "insert into sandwich (slot, frontrun_tx, backrun_tx, quote_mint, profit, leftover, fees, tips, net_profit, loss_sol, profit_sol, rule_set, rule_version, cross_slot, confidence, sandwicher_id, frontrun_tip, backrun_tip, bundle_id, bundled) values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) on duplicate key update id = last_insert_id(id), quote_mint = values(quote_mint), profit = values(profit), leftover = values(leftover), fees = values(fees), tips = values(tips), net_profit = values(net_profit), loss_sol = values(loss_sol), profit_sol = values(profit_sol), rule_version = values(rule_version), cross_slot = values(cross_slot), confidence = values(confidence), sandwicher_id = values(sandwicher_id), frontrun_tip = values(frontrun_tip), backrun_tip = values(backrun_tip), bundle_id = values(bundle_id), bundled = values(bundled)";
+const INSERT_SWAP: &str = "insert into swap (sandwich_id, outer_program, inner_program, amm, subject, input_mint, output_mint, input_amount, output_amount, input_decimals, output_decimals, input_ui_amount, output_ui_amount, tx_id, swap_type) values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)";
+
+impl Into<Value> for SlotStatus {
+    fn into(self) -> Value {
+        Value::from(self.as_str())
+    }
+}
+
+impl Into<Value> for SwapType {
+    fn into(self) -> Value {
+        Value::from(self.as_str())
+    }
+}
+
+/// Mysql/MariaDB through a connection pool, connections are checked and reestablished as they're taken from the pool.
+/// Statements are prepared once per connection by its statement cache.
+pub struct MysqlStore {
+    pool: Pool,
+    tx_ids: TxIdCache,
+}
+
+impl MysqlStore {
+    /// Connections are only opened when needed
+    pub fn new(url: &str) -> Result<Self, StorageError> {
+        let opts = Opts::from_url(url).map_err(|e| StorageError::Mysql(mysql::Error::UrlError(e)))?;
+        let pool_opts = PoolOpts::default().with_constraints(PoolConstraints::new(0, POOL_SIZE).unwrap());
+        Ok(Self {
+            pool: Pool::new(OptsBuilder::from_opts(opts).tcp_connect_timeout(Some(CONNECT_TIMEOUT)).pool_opts(pool_opts))?,
+            tx_ids: TxIdCache::default(),
+        })
+    }
+}
+
+impl SandwichStore for MysqlStore {
+    fn backend(&self) -> Backend {
+        Backend::Mysql
+    }
+
+    fn write_block(&mut self, block: &DbBlock, sandwiches: &[Sandwich]) -> Result<(), StorageError> {
+        let mut conn = self.pool.get_conn()?;
+        let mut dbtx = conn.start_transaction(TxOpts::default())?;
+        dbtx.exec_drop(UPSERT_BLOCK, (block.slot, block.ts, block.tx_count))?;
+        if sandwiches.is_empty() {
+            dbtx.commit()?;
+            return Ok(());
+        }
+        let swaps = sandwiches.iter().map(sandwich_swaps).collect::<Vec<_>>();
+        // figure out which txs aren't cached, a tx may appear in several sandwiches
+        let mut new_txs: HashMap<&str, &Swap> = HashMap::new();
+        swaps.iter().flatten().filter(|swap| self.tx_ids.get(&swap.0.sig).is_none()).for_each(|swap| {
+            new_txs.insert(swap.0.sig.as_str(), swap.0);
+        });
+        // ids of the txs upserted here only make it to the cache once committed
+        let mut new_tx_ids: HashMap<String, u64> = HashMap::new();
+        if !new_txs.is_empty() {
+            dbtx.exec_batch(UPSERT_TX, new_txs.values().map(|swap| (&swap.sig, &swap.signer, swap.slot, swap.order)))?;
+            // look up their ids with a select
+            let q_marks = new_txs.keys().map(|_| "?").collect::<Vec<_>>().join(",");
+            let stmt = dbtx.prep(format!("select id, tx_hash from transaction where tx_hash in ({q_marks})"))?;
+            let _ = dbtx.exec_map(&stmt, new_txs.keys().copied().collect::<Vec<_>>(), |(id, tx_hash)| {
+                new_tx_ids.insert(tx_hash, id);
+            })?;
+        }
+        let mut sandwich_ids = Vec::with_capacity(sandwiches.len());
+        for sandwich in sandwiches {
+            let profit = &sandwich.profit;
+            // too many columns for a tuple
+            let row: Vec<Value> = vec![sandwich.slot.into(), sandwich.frontrun.sig.as_str().into(), sandwich.backrun.sig.as_str().into(), profit.quote_mint.as_str().into(), profit.gross.into(), profit.leftover.into(), profit.fees.into(), profit.tips.into(), profit.net.into(), sandwich.loss_sol.into(), sandwich.profit_sol.into(), sandwich.rule_set.as_str().into(), sandwich.rule_version.into(), sandwich.is_cross_slot().into(), sandwich.confidence.into(), sandwich.sandwicher_id.into(), sandwich.frontrun_tip.into(), sandwich.backrun_tip.into(), sandwich.bundle_id.as_deref().into(), sandwich.bundled.into()];
+            dbtx.exec_drop(UPSERT_SANDWICH, row)?;
+            sandwich_ids.push(dbtx.last_insert_id().ok_or(StorageError::MissingSandwichId)?);
+        }
+        // replace the swaps of the sandwiches
+        let q_marks = sandwich_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+        dbtx.exec_drop(format!("delete from swap where sandwich_id in ({q_marks})"), sandwich_ids.clone())?;
+        let rows = sandwich_ids.iter().zip(swaps.iter()).flat_map(|(sandwich_id, swaps)| swaps.iter().map(move |swap| (sandwich_id, swap))).map(|(sandwich_id, swap)| {
+            let tx_id = self.tx_ids.get(&swap.0.sig).or_else(|| new_tx_ids.get(&swap.0.sig).copied()).ok_or_else(|| StorageError::MissingTxId(swap.0.sig.clone()))?;
+            // too many columns for a tuple
+            let row: Vec<Value> = vec![(*sandwich_id).into(), swap.0.outer_program.as_deref().into(), swap.0.program.as_str().into(), swap.0.amm.as_str().into(), swap.0.subject.as_str().into(), swap.0.input_mint.as_str().into(), swap.0.output_mint.as_str().into(), swap.0.input_amount.into(), swap.0.output_amount.into(), swap.0.input_decimals.into(), swap.0.output_decimals.into(), swap.0.input_ui_amount.into(), swap.0.output_ui_amount.into(), tx_id.into(), swap.1.clone().into()];
+            Ok(row)
+        }).collect::<Result<Vec<_>, StorageError>>()?;
+        dbtx.exec_batch(INSERT_SWAP, rows)?;
+        // remember what linked the sandwichers so the clusters can be restored
+        dbtx.exec_batch(INSERT_MEMBER, sandwiches.iter().filter_map(|sandwich| Some((sandwich, sandwich.sandwicher_id?))).flat_map(|(sandwich, sandwicher_id)| {
+            sandwich.sandwicher_members.iter().map(move |member| (member, sandwicher_id))
+        }))?;
+        // merged entities keep the oldest id, which replaces the merged ones on the sandwiches stored so far
+        for sandwich in sandwiches.iter().filter(|sandwich| !sandwich.merged_sandwicher_ids.is_empty()) {
+            let Some(sandwicher_id) = sandwich.sandwicher_id else {
+                continue;
+            };
+            let q_marks = sandwich.merged_sandwicher_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+            let params = std::iter::once(sandwicher_id).chain(sandwich.merged_sandwicher_ids.iter().copied()).collect::<Vec<_>>();
+            dbtx.exec_drop(format!("update sandwich set sandwicher_id = ? where sandwicher_id in ({q_marks})"), params)?;
+        }
+        dbtx.commit()?;
+        self.tx_ids.extend(&new_txs, new_tx_ids);
+        Ok(())
+    }
+
+    fn write_slot_status(&mut self, slot: u64, status: SlotStatus) -> Result<(), StorageError> {
+        self.pool.get_conn()?.exec_drop(INSERT_SLOT, (slot, status))?;
+        Ok(())
+    }
+
+    fn observed_slots(&mut self, start: u64, end: u64) -> Result<HashSet<u64>, StorageError> {
+        let slots: Vec<u64> = self.pool.get_conn()?.exec("select slot from block where slot between ? and ? union select slot from slot_status where slot between ? and ? and status != 'MISSED'", (start, end, start, end))?;
+        Ok(slots.into_iter().collect())
+    }
+
+    fn last_observed_slot(&mut self) -> Result<Option<u64>, StorageError> {
+        let slot: Option<Option<u64>> = self.pool.get_conn()?.query_first("select max(slot) from (select max(slot) as slot from block union all select max(slot) from slot_status where status != 'MISSED') s")?;
+        Ok(slot.flatten())
+    }
+
+    fn sandwicher_members(&mut self) -> Result<Vec<(String, u64)>, StorageError> {
+        Ok(self.pool.get_conn()?.exec("select member, sandwicher_id from sandwicher_member order by sandwicher_id", ())?)
+    }
+
+    fn insert_leader_schedule(&mut self, leader_schedule: &HashMap<u64, &String>) -> Result<(), StorageError> {
+        let mut conn = self.pool.get_conn()?;
+        // insert in batches of 1600 rows
+        let stmt = "INSERT INTO leader_schedule (slot, leader) VALUES ";
+        let mut query = String::from(stmt);
+        let mut count = 0;
+        let mut cum_count = 0;
+        for (slot, leader) in leader_schedule.iter() {
+            query.push_str(&format!("({}, '{}'),", slot, leader));
+            count += 1;
+            cum_count += 1;
+            if count == 1600 {
+                query.pop();
+                conn.exec_drop(query, ())?;
+                query = String::from(stmt);
+                count = 0;
+                println!("inserted {}/{}", cum_count, leader_schedule.len());
+            }
+        }
+        if count > 0 {
+            query.pop();
+            conn.exec_drop(query, ())?;
+        }
+        Ok(())
+    }
+
+    fn for_each_slot_leader(&mut self, start: u64, end: u64, status: SlotStatus, f: &mut dyn FnMut(u64, &str)) -> Result<(), StorageError> {
+        let mut conn = self.pool.get_conn()?;
+        for row in conn.exec_iter("select l.slot, l.leader from leader_schedule l, slot_status s where l.slot = s.slot and s.status = ? and l.slot between ? and ?", (status, start, end))? {
+            let (slot, leader): (u64, String) = mysql::from_row(row?);
+            f(slot, &leader);
+        }
+        Ok(())
+    }
+
+    fn slot_count(&mut self, start: u64, end: u64, status: SlotStatus) -> Result<u64, StorageError> {
+        let count: Option<u64> = self.pool.get_conn()?.exec_first("select count(*) from slot_status where status = ? and slot between ? and ?", (status, start, end))?;
+        Ok(count.unwrap_or(0))
+    }
+
+    fn sandwiches_per_slot(&mut self, start: u64, end: u64, rule_set: &str, min_confidence: f64) -> Result<Vec<(u64, u64)>, StorageError> {
+        Ok(self.pool.get_conn()?.exec("select slot, count(*) from sandwich where rule_set = ? and confidence >= ? and slot between ? and ? group by slot", (rule_set, min_confidence, start, end))?)
+    }
+
+    fn schema_version(&mut self) -> Result<u32, StorageError> {
+        let mut conn = self.pool.get_conn()?;
+        conn.query_drop("create table if not exists schema_migrations (version int unsigned not null primary key, name varchar(100) not null, applied_at timestamp not null default current_timestamp)")?;
+        conn.query_drop("create table if not exists schema_migration_progress (version int unsigned not null primary key, statements int unsigned not null)")?;
+        let version: Option<Option<u32>> = conn.query_first("select max(version) from schema_migrations")?;
+        Ok(version.flatten().unwrap_or(0))
+    }
+
+    /// DDL can't be rolled back in mysql, so the statements of a migration are recorded as they succeed
+    /// and a failed migration picks up at the statement that failed when it's applied again.
+    /// Only a statement interrupted before it was recorded runs twice.
+    fn apply_migration(&mut self, migration: &Migration) -> Result<(), StorageError> {
+        let mut conn = self.pool.get_conn()?;
+        let done: Option<usize> = conn.exec_first("select statements from schema_migration_progress where version = ?", (migration.version,))?;
+        for (i, statement) in statements(migration.sql(Backend::Mysql)).iter().enumerate().skip(done.unwrap_or(0)) {
+            conn.query_drop(statement)?;
+            conn.exec_drop("insert into schema_migration_progress (version, statements) values (?, ?) on duplicate key update statements = values(statements)", (migration.version, i + 1))?;
+        }
+        let mut dbtx = conn.start_transaction(TxOpts::default())?;
+        dbtx.exec_drop("insert into schema_migrations (version, name) values (?, ?)", (migration.version, migration.name))?;
+        dbtx.exec_drop("delete from schema_migration_progress where version = ?", (migration.version,))?;
+        dbtx.commit()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::{HashMap, HashSet};
+
+use postgres::{fallible_iterator::FallibleIterator, types::ToSql, Client, Config, NoTls};
+
+use crate::{decode::Swap, detect::Sandwich, migrate::Migration};
+
+use super::{sandwich_swaps, Backend, DbBlock, SandwichStore, SlotStatus, StorageError, TxIdCache, CONNECT_TIMEOUT};
+
+// postgres has no unsigned types, u64s are stored as bigint and u32s as integer
+const UPSERT_BLOCK: &str = "insert into block (slot, \"timestamp\", tx_count) values ($1, $2, $3) on conflict (slot) do update set \"timestamp\" = excluded.\"timestamp\", tx_count = excluded.tx_count";
+const UPSERT_TX: &str = "insert into \"transaction\" (tx_hash, signer, slot, order_in_block) values ($1, $2, $3, $4) on conflict (tx_hash) do update set signer = excluded.signer, slot = excluded.slot, order_in_block = excluded.order_in_block returning id";
+const INSERT_SLOT: &str = "insert into slot_status (slot, status) values ($1, $2) on conflict (slot) do update set status = excluded.status where excluded.status <> 'MISSED'";
+const INSERT_MEMBER: &str = "insert into sandwicher_member (member, sandwicher_id) values ($1, $2) on conflict do nothing";
+const UPSERT_SANDWICH: &str = "insert into sandwich (slot, frontrun_tx, backrun_tx, quote_mint, profit, leftover, fees, tips, net_profit, loss_sol, profit_sol, rule_set, rule_version, cross_slot, confidence, sandwicher_id, frontrun_tip, backrun_tip, bundle_id, bundled) values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20) on conflict (slot, frontrun_tx, backrun_tx, rule_set) do update set quote_mint = excluded.quote_mint, profit = excluded.profit, leftover = excluded.leftover, fees = excluded.fees, tips = excluded.tips, net_profit = excluded.net_profit, loss_sol = excluded.loss_sol, profit_sol = excluded.profit_sol, rule_version = excluded.rule_version, cross_slot = excluded.cross_slot, confidence = excluded.confidence, sandwicher_id = excluded.sandwicher_id, frontrun_tip = excluded.frontrun_tip, backrun_tip = excluded.backrun_tip, bundle_id = excluded.bundle_id, bundled = excluded.bundled returning id";
+const INSERT_SWAP: &str = "insert into swap (sandwich_id, outer_program, inner_program, amm, subject, input_mint, output_mint, input_amount, output_amount, input_decimals, output_decimals, input_ui_amount, output_ui_amount, tx_id, swap_type) values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)";
+
+/// PostgreSQL through a single connection, reestablished on the next write once it's closed
+pub struct PostgresStore {
+    config: Config,
+    client: Option<Client>,
+    tx_ids: TxIdCache,
+}
+
+impl PostgresStore {
+    /// The connection is only opened when needed
+    pub fn new(url: &str) -> Result<Self, StorageError> {
+        let mut config: Config = url.parse()?;
+        config.connect_timeout(CONNECT_TIMEOUT);
+        Ok(Self {
+            config,
+            client: None,
+            tx_ids: TxIdCache::default(),
+        })
+    }
+
+    fn client(&mut self) -> Result<&mut Client, StorageError> {
+        connect(&self.config, &mut self.client)
+    }
+}
+
+/// Separate from the store so the connection can be borrowed along with its other fields
+fn connect<'a>(config: &Config, client: &'a mut Option<Client>) -> Result<&'a mut Client, StorageError> {
+    if client.as_ref().map_or(true, |client| client.is_closed()) {
+        *client = Some(config.connect(NoTls)?);
+    }
+    Ok(client.as_mut().unwrap())
+}
+
+impl SandwichStore for PostgresStore {
+    fn backend(&self) -> Backend {
+        Backend::Postgres
+    }
+
+    fn write_block(&mut self, block: &DbBlock, sandwiches: &[Sandwich]) -> Result<(), StorageError> {
+        let mut dbtx = connect(&self.config, &mut self.client)?.transaction()?;
+        dbtx.execute(UPSERT_BLOCK, &[&(block.slot as i64), &block.ts, &(block.tx_count as i32)])?;
+        if sandwiches.is_empty() {
+            dbtx.commit()?;
+            return Ok(());
+        }
+        let swaps = sandwiches.iter().map(sandwich_swaps).collect::<Vec<_>>();
+        // figure out which txs aren't cached, a tx may appear in several sandwiches
+        let mut new_txs: HashMap<&str, &Swap> = HashMap::new();
+        swaps.iter().flatten().filter(|swap| self.tx_ids.get(&swap.0.sig).is_none()).for_each(|swap| {
+            new_txs.insert(swap.0.sig.as_str(), swap.0);
+        });
+        // ids of the txs upserted here only make it to the cache once committed
+        let mut new_tx_ids: HashMap<String, u64> = HashMap::new();
+        if !new_txs.is_empty() {
+            let stmt = dbtx.prepare(UPSERT_TX)?;
+            for swap in new_txs.values() {
+                let id: i64 = dbtx.query_one(&stmt, &[&swap.sig, &swap.signer, &(swap.slot as i64), &(swap.order as i32)])?.try_get(0)?;
+                new_tx_ids.insert(swap.sig.clone(), id as u64);
+            }
+        }
+        let stmt = dbtx.prepare(UPSERT_SANDWICH)?;
+        let mut sandwich_ids: Vec<i64> = Vec::with_capacity(sandwiches.len());
+        for sandwich in sandwiches {
+            let profit = &sandwich.profit;
+            let row = dbtx.query_opt(&stmt, &[&(sandwich.slot as i64), &sandwich.frontrun.sig, &sandwich.backrun.sig, &profit.quote_mint, &profit.gross, &profit.leftover, &(profit.fees as i64), &(profit.tips as i64), &profit.net, &sandwich.loss_sol, &sandwich.profit_sol, &sandwich.rule_set, &(sandwich.rule_version as i32), &sandwich.is_cross_slot(), &sandwich.confidence, &sandwich.sandwicher_id.map(|id| id as i64), &(sandwich.frontrun_tip as i64), &(sandwich.backrun_tip as i64), &sandwich.bundle_id, &sandwich.bundled])?;
+            sandwich_ids.push(row.ok_or(StorageError::MissingSandwichId)?.try_get(0)?);
+        }
+        // replace the swaps of the sandwiches
+        dbtx.execute("delete from swap where sandwich_id = any($1)", &[&sandwich_ids])?;
+        let stmt = dbtx.prepare(INSERT_SWAP)?;
+        for (sandwich_id, swaps) in sandwich_ids.iter().zip(swaps.iter()) {
+            for (swap, swap_type) in swaps {
+                let tx_id = self.tx_ids.get(&swap.sig).or_else(|| new_tx_ids.get(&swap.sig).copied()).ok_or_else(|| StorageError::MissingTxId(swap.sig.clone()))?;
+                dbtx.execute(&stmt, &[sandwich_id, &swap.outer_program, &swap.program, &swap.amm, &swap.subject, &swap.input_mint, &swap.output_mint, &swap.input_amount.to_string(), &swap.output_amount.to_string(), &swap.input_decimals.map(i16::from), &swap.output_decimals.map(i16::from), &swap.input_ui_amount, &swap.output_ui_amount, &(tx_id as i64), &swap_type.as_str()])?;
+            }
+        }
+        // remember what linked the sandwichers so the clusters can be restored
+        let stmt = dbtx.prepare(INSERT_MEMBER)?;
+        for sandwich in sandwiches {
+            let Some(sandwicher_id) = sandwich.sandwicher_id else {
+                continue;
+            };
+            for member in sandwich.sandwicher_members.iter() {
+                dbtx.execute(&stmt, &[member, &(sandwicher_id as i64)])?;
+            }
+        }
+        // merged entities keep the oldest id, which replaces the merged ones on the sandwiches stored so far
+        for sandwich in sandwiches.iter().filter(|sandwich| !sandwich.merged_sandwicher_ids.is_empty()) {
+            let Some(sandwicher_id) = sandwich.sandwicher_id else {
+                continue;
+            };
+            let merged_ids = sandwich.merged_sandwicher_ids.iter().map(|id| *id as i64).collect::<Vec<_>>();
+            dbtx.execute("update sandwich set sandwicher_id = $1 where sandwicher_id = any($2)", &[&(sandwicher_id as i64), &merged_ids])?;
+        }
+        dbtx.commit()?;
+        self.tx_ids.extend(&new_txs, new_tx_ids);
+        Ok(())
+    }
+
+    fn write_slot_status(&mut self, slot: u64, status: SlotStatus) -> Result<(), StorageError> {
+        self.client()?.execute(INSERT_SLOT, &[&(slot as i64), &status.as_str()])?;
+        Ok(())
+    }
+
+    fn observed_slots(&mut self, start: u64, end: u64) -> Result<HashSet<u64>, StorageError> {
+        let rows = self.client()?.query("select slot from block where slot between $1 and $2 union select slot from slot_status where slot between $1 and $2 and status <> 'MISSED'", &[&(start as i64), &(end as i64)])?;
+        Ok(rows.iter().map(|row| row.try_get::<_, i64>(0).map(|slot| slot as u64)).collect::<Result<_, _>>()?)
+    }
+
+    fn last_observed_slot(&mut self) -> Result<Option<u64>, StorageError> {
+        let slot: Option<i64> = self.client()?.query_one("select max(slot) from (select max(slot) as slot from block union all select max(slot) from slot_status where status <> 'MISSED') s", &[])?.try_get(0)?;
+        Ok(slot.map(|slot| slot as u64))
+    }
+
+    fn sandwicher_members(&mut self) -> Result<Vec<(String, u64)>, StorageError> {
+        let rows = self.client()?.query("select member, sandwicher_id from sandwicher_member order by sandwicher_id", &[])?;
+        Ok(rows.iter().map(|row| Ok((row.try_get(0)?, row.try_get::<_, i64>(1)? as u64))).collect::<Result<_, postgres::Error>>()?)
+    }
+
+    fn insert_leader_schedule(&mut self, leader_schedule: &HashMap<u64, &String>) -> Result<(), StorageError> {
+        let mut dbtx = self.client()?.transaction()?;
+        let stmt = dbtx.prepare("insert into leader_schedule (slot, leader) values ($1, $2)")?;
+        for (i, (slot, leader)) in leader_schedule.iter().enumerate() {
+            dbtx.execute(&stmt, &[&(*slot as i64), leader])?;
+            if (i + 1) % 1600 == 0 {
+                println!("inserted {}/{}", i + 1, leader_schedule.len());
+            }
+        }
+        dbtx.commit()?;
+        Ok(())
+    }
+
+    fn for_each_slot_leader(&mut self, start: u64, end: u64, status: SlotStatus, f: &mut dyn FnMut(u64, &str)) -> Result<(), StorageError> {
+        let (status, start, end) = (status.as_str(), start as i64, end as i64);
+        let params: [&(dyn ToSql + Sync); 3] = [&status, &start, &end];
+        let mut rows = self.client()?.query_raw("select l.slot, l.leader from leader_schedule l, slot_status s where l.slot = s.slot and s.status = $1 and l.slot between $2 and $3", params)?;
+        while let Some(row) = rows.next()? {
+            f(row.try_get::<_, i64>(0)? as u64, row.try_get(1)?);
+        }
+        Ok(())
+    }
+
+    fn slot_count(&mut self, start: u64, end: u64, status: SlotStatus) -> Result<u64, StorageError> {
+        let row = self.client()?.query_one("select count(*) from slot_status where status = $1 and slot between $2 and $3", &[&status.as_str(), &(start as i64), &(end as i64)])?;
+        Ok(row.try_get::<_, i64>(0)? as u64)
+    }
+
+    fn sandwiches_per_slot(&mut self, start: u64, end: u64, rule_set: &str, min_confidence: f64) -> Result<Vec<(u64, u64)>, StorageError> {
+        let rows = self.client()?.query("select slot, count(*) from sandwich where rule_set = $1 and confidence >= $2 and slot between $3 and $4 group by slot", &[&rule_set, &min_confidence, &(start as i64), &(end as i64)])?;
+        Ok(rows.iter().map(|row| Ok((row.try_get::<_, i64>(0)? as u64, row.try_get::<_, i64>(1)? as u64))).collect::<Result<_, postgres::Error>>()?)
+    }
+
+    fn schema_version(&mut self) -> Result<u32, StorageError> {
+        let client = self.client()?;
+        client.batch_execute("create table if not exists schema_migrations (version integer not null primary key, name varchar(100) not null, applied_at timestamp not null default current_timestamp)")?;
+        let version: Option<i32> = client.query_one("select max(version) from schema_migrations", &[])?.try_get(0)?;
+        Ok(version.unwrap_or(0) as u32)
+    }
+
+    /// DDL is transactional in postgres, so a failed migration leaves nothing behind
+    fn apply_migration(&mut self, migration: &Migration) -> Result<(), StorageError> {
+        let mut dbtx = self.client()?.transaction()?;
+        dbtx.batch_execute(migration.sql(Backend::Postgres))?;
+        dbtx.execute("insert into schema_migrations (version, name) values ($1, $2)", &[&(migration.version as i32), &migration.name])?;
+        dbtx.commit()?;
+        Ok(())
+    }
+}
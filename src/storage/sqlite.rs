@@ -0,0 +1,228 @@
+use std::{collections::{HashMap, HashSet}, path::Path, time::Duration};
+
+use rusqlite::{params, params_from_iter, Connection};
+
+use crate::{decode::Swap, detect::Sandwich, migrate::Migration};
+
+use super::{sandwich_swaps, Backend, DbBlock, SandwichStore, SlotStatus, StorageError, TxIdCache};
+
+/// How long a write waits for another connection to release the db before failing
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const UPSERT_BLOCK: &str = "insert into block (slot, timestamp, tx_count) values (?1, ?2, ?3) on conflict (slot) do update set timestamp = excluded.timestamp, tx_count = excluded.tx_count";
+const UPSERT_TX: &str = "insert into \"transaction\" (tx_hash, signer, slot, order_in_block) values (?1, ?2, ?3, ?4) on conflict (tx_hash) do update set signer = excluded.signer, slot = excluded.slot, order_in_block = excluded.order_in_block returning id";
+const INSERT_SLOT: &str = "insert into slot_status (slot, status) values (?1, ?2) on conflict (slot) do update set status = excluded.status where excluded.status <> 'MISSED'";
+const INSERT_MEMBER: &str = "insert into sandwicher_member (member, sandwicher_id) values (?1, ?2) on conflict do nothing";
+const UPSERT_SANDWICH: &str = "insert into sandwich (slot, frontrun_tx, backrun_tx, quote_mint, profit, leftover, fees, tips, net_profit, loss_sol, profit_sol, rule_set, rule_version, cross_slot, confidence, sandwicher_id, frontrun_tip, backrun_tip, bundle_id, bundled) values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20) on conflict (slot, frontrun_tx, backrun_tx, rule_set) do update set quote_mint = excluded.quote_mint, profit = excluded.profit, leftover = excluded.leftover, fees = excluded.fees, tips = excluded.tips, net_profit = excluded.net_profit, loss_sol = excluded.loss_sol, profit_sol = excluded.profit_sol, rule_version = excluded.rule_version, cross_slot = excluded.cross_slot, confidence = excluded.confidence, sandwicher_id = excluded.sandwicher_id, frontrun_tip = excluded.frontrun_tip, backrun_tip = excluded.backrun_tip, bundle_id = excluded.bundle_id, bundled = excluded.bundled returning id";
+const INSERT_SWAP: &str = "insert into swap (sandwich_id, outer_program, inner_program, amm, subject, input_mint, output_mint, input_amount, output_amount, input_decimals, output_decimals, input_ui_amount, output_ui_amount, tx_id, swap_type) values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)";
+
+/// Embedded SQLite db, for small deployments and tests that don't have a db server
+pub struct SqliteStore {
+    conn: Connection,
+    tx_ids: TxIdCache,
+}
+
+impl SqliteStore {
+    pub fn open(path: &Path) -> Result<Self, StorageError> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// A db that only lives as long as the store
+    pub fn in_memory() -> Result<Self, StorageError> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self, StorageError> {
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        // off by default in sqlite
+        conn.execute_batch("pragma foreign_keys = on;")?;
+        Ok(Self {
+            conn,
+            tx_ids: TxIdCache::default(),
+        })
+    }
+}
+
+impl SandwichStore for SqliteStore {
+    fn backend(&self) -> Backend {
+        Backend::Sqlite
+    }
+
+    fn write_block(&mut self, block: &DbBlock, sandwiches: &[Sandwich]) -> Result<(), StorageError> {
+        let dbtx = self.conn.transaction()?;
+        dbtx.execute(UPSERT_BLOCK, params![block.slot as i64, block.ts, block.tx_count as i64])?;
+        if sandwiches.is_empty() {
+            dbtx.commit()?;
+            return Ok(());
+        }
+        let swaps = sandwiches.iter().map(sandwich_swaps).collect::<Vec<_>>();
+        // figure out which txs aren't cached, a tx may appear in several sandwiches
+        let mut new_txs: HashMap<&str, &Swap> = HashMap::new();
+        swaps.iter().flatten().filter(|swap| self.tx_ids.get(&swap.0.sig).is_none()).for_each(|swap| {
+            new_txs.insert(swap.0.sig.as_str(), swap.0);
+        });
+        // ids of the txs upserted here only make it to the cache once committed
+        let mut new_tx_ids: HashMap<String, u64> = HashMap::new();
+        let mut sandwich_ids: Vec<i64> = Vec::with_capacity(sandwiches.len());
+        // statements borrow the transaction until it's committed
+        {
+            let mut stmt = dbtx.prepare_cached(UPSERT_TX)?;
+            for swap in new_txs.values() {
+                let id: i64 = stmt.query_row(params![swap.sig, swap.signer, swap.slot as i64, swap.order as i64], |row| row.get(0))?;
+                new_tx_ids.insert(swap.sig.clone(), id as u64);
+            }
+            let mut stmt = dbtx.prepare_cached(UPSERT_SANDWICH)?;
+            for sandwich in sandwiches {
+                let profit = &sandwich.profit;
+                let id: Option<i64> = stmt.query_row(params![sandwich.slot as i64, sandwich.frontrun.sig, sandwich.backrun.sig, profit.quote_mint, profit.gross, profit.leftover, profit.fees as i64, profit.tips as i64, profit.net, sandwich.loss_sol, sandwich.profit_sol, sandwich.rule_set, sandwich.rule_version, sandwich.is_cross_slot(), sandwich.confidence, sandwich.sandwicher_id.map(|id| id as i64), sandwich.frontrun_tip as i64, sandwich.backrun_tip as i64, sandwich.bundle_id, sandwich.bundled], |row| row.get(0))?;
+                sandwich_ids.push(id.ok_or(StorageError::MissingSandwichId)?);
+            }
+            // replace the swaps of the sandwiches
+            let q_marks = sandwich_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+            dbtx.execute(&format!("delete from swap where sandwich_id in ({q_marks})"), params_from_iter(sandwich_ids.iter()))?;
+            let mut stmt = dbtx.prepare_cached(INSERT_SWAP)?;
+            for (sandwich_id, swaps) in sandwich_ids.iter().zip(swaps.iter()) {
+                for (swap, swap_type) in swaps {
+                    let tx_id = self.tx_ids.get(&swap.sig).or_else(|| new_tx_ids.get(&swap.sig).copied()).ok_or_else(|| StorageError::MissingTxId(swap.sig.clone()))?;
+                    stmt.execute(params![sandwich_id, swap.outer_program, swap.program, swap.amm, swap.subject, swap.input_mint, swap.output_mint, swap.input_amount.to_string(), swap.output_amount.to_string(), swap.input_decimals, swap.output_decimals, swap.input_ui_amount, swap.output_ui_amount, tx_id as i64, swap_type.as_str()])?;
+                }
+            }
+            // remember what linked the sandwichers so the clusters can be restored
+            let mut stmt = dbtx.prepare_cached(INSERT_MEMBER)?;
+            for sandwich in sandwiches {
+                let Some(sandwicher_id) = sandwich.sandwicher_id else {
+                    continue;
+                };
+                for member in sandwich.sandwicher_members.iter() {
+                    stmt.execute(params![member, sandwicher_id as i64])?;
+                }
+            }
+            // merged entities keep the oldest id, which replaces the merged ones on the sandwiches stored so far
+            for sandwich in sandwiches.iter().filter(|sandwich| !sandwich.merged_sandwicher_ids.is_empty()) {
+                let Some(sandwicher_id) = sandwich.sandwicher_id else {
+                    continue;
+                };
+                let q_marks = sandwich.merged_sandwicher_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+                let params = std::iter::once(sandwicher_id).chain(sandwich.merged_sandwicher_ids.iter().copied()).map(|id| id as i64);
+                dbtx.execute(&format!("update sandwich set sandwicher_id = ? where sandwicher_id in ({q_marks})"), params_from_iter(params))?;
+            }
+        }
+        dbtx.commit()?;
+        self.tx_ids.extend(&new_txs, new_tx_ids);
+        Ok(())
+    }
+
+    fn write_slot_status(&mut self, slot: u64, status: SlotStatus) -> Result<(), StorageError> {
+        self.conn.execute(INSERT_SLOT, params![slot as i64, status.as_str()])?;
+        Ok(())
+    }
+
+    fn observed_slots(&mut self, start: u64, end: u64) -> Result<HashSet<u64>, StorageError> {
+        let mut stmt = self.conn.prepare("select slot from block where slot between ?1 and ?2 union select slot from slot_status where slot between ?1 and ?2 and status <> 'MISSED'")?;
+        let slots = stmt.query_map(params![start as i64, end as i64], |row| row.get::<_, i64>(0))?;
+        Ok(slots.map(|slot| slot.map(|slot| slot as u64)).collect::<Result<_, _>>()?)
+    }
+
+    fn last_observed_slot(&mut self) -> Result<Option<u64>, StorageError> {
+        let slot: Option<i64> = self.conn.query_row("select max(slot) from (select max(slot) as slot from block union all select max(slot) from slot_status where status <> 'MISSED') s", [], |row| row.get(0))?;
+        Ok(slot.map(|slot| slot as u64))
+    }
+
+    fn sandwicher_members(&mut self) -> Result<Vec<(String, u64)>, StorageError> {
+        let mut stmt = self.conn.prepare("select member, sandwicher_id from sandwicher_member order by sandwicher_id")?;
+        let members = stmt.query_map([], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as u64)))?;
+        Ok(members.collect::<Result<_, _>>()?)
+    }
+
+    fn insert_leader_schedule(&mut self, leader_schedule: &HashMap<u64, &String>) -> Result<(), StorageError> {
+        let dbtx = self.conn.transaction()?;
+        {
+            let mut stmt = dbtx.prepare("insert into leader_schedule (slot, leader) values (?1, ?2)")?;
+            for (i, (slot, leader)) in leader_schedule.iter().enumerate() {
+                stmt.execute(params![*slot as i64, leader])?;
+                if (i + 1) % 1600 == 0 {
+                    println!("inserted {}/{}", i + 1, leader_schedule.len());
+                }
+            }
+        }
+        dbtx.commit()?;
+        Ok(())
+    }
+
+    fn for_each_slot_leader(&mut self, start: u64, end: u64, status: SlotStatus, f: &mut dyn FnMut(u64, &str)) -> Result<(), StorageError> {
+        let mut stmt = self.conn.prepare("select l.slot, l.leader from leader_schedule l, slot_status s where l.slot = s.slot and s.status = ?1 and l.slot between ?2 and ?3")?;
+        let mut rows = stmt.query(params![status.as_str(), start as i64, end as i64])?;
+        while let Some(row) = rows.next()? {
+            let leader: String = row.get(1)?;
+            f(row.get::<_, i64>(0)? as u64, &leader);
+        }
+        Ok(())
+    }
+
+    fn slot_count(&mut self, start: u64, end: u64, status: SlotStatus) -> Result<u64, StorageError> {
+        let count: i64 = self.conn.query_row("select count(*) from slot_status where status = ?1 and slot between ?2 and ?3", params![status.as_str(), start as i64, end as i64], |row| row.get(0))?;
+        Ok(count as u64)
+    }
+
+    fn sandwiches_per_slot(&mut self, start: u64, end: u64, rule_set: &str, min_confidence: f64) -> Result<Vec<(u64, u64)>, StorageError> {
+        let mut stmt = self.conn.prepare("select slot, count(*) from sandwich where rule_set = ?1 and confidence >= ?2 and slot between ?3 and ?4 group by slot")?;
+        let counts = stmt.query_map(params![rule_set, min_confidence, start as i64, end as i64], |row| Ok((row.get::<_, i64>(0)? as u64, row.get::<_, i64>(1)? as u64)))?;
+        Ok(counts.collect::<Result<_, _>>()?)
+    }
+
+    fn schema_version(&mut self) -> Result<u32, StorageError> {
+        self.conn.execute_batch("create table if not exists schema_migrations (version integer not null primary key, name text not null, applied_at text not null default current_timestamp);")?;
+        let version: Option<u32> = self.conn.query_row("select max(version) from schema_migrations", [], |row| row.get(0))?;
+        Ok(version.unwrap_or(0))
+    }
+
+    /// DDL is transactional in sqlite, so a failed migration leaves nothing behind
+    fn apply_migration(&mut self, migration: &Migration) -> Result<(), StorageError> {
+        let dbtx = self.conn.transaction()?;
+        dbtx.execute_batch(migration.sql(Backend::Sqlite))?;
+        dbtx.execute("insert into schema_migrations (version, name) values (?1, ?2)", params![migration.version, migration.name])?;
+        dbtx.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode::fixtures::{swap, MINT_A, MINT_B}, migrate::migrate, rules::RuleSet};
+
+    fn sandwich(slot: u64, sandwicher_id: u64, merged_sandwicher_ids: Vec<u64>) -> Sandwich {
+        let leg = |leg: &str| Swap {
+            sig: format!("{leg}-{slot}-{sandwicher_id}"),
+            slot,
+            ..swap(MINT_A, MINT_B, 100, 100)
+        };
+        let mut sandwich = Sandwich::new(slot, leg("frontrun"), vec![leg("victim")], leg("backrun"), 0, &RuleSet::default()).unwrap();
+        sandwich.sandwicher_id = Some(sandwicher_id);
+        sandwich.merged_sandwicher_ids = merged_sandwicher_ids;
+        sandwich
+    }
+
+    #[test]
+    fn last_observed_slot_ignores_missed_slots() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        migrate(&mut store).unwrap();
+        assert_eq!(store.last_observed_slot().unwrap(), None);
+        store.write_block(&DbBlock { slot: 10, ts: 0, tx_count: 0 }, &[]).unwrap();
+        assert_eq!(store.last_observed_slot().unwrap(), Some(10));
+        store.write_slot_status(12, SlotStatus::Observed).unwrap();
+        store.write_slot_status(15, SlotStatus::Missed).unwrap();
+        assert_eq!(store.last_observed_slot().unwrap(), Some(12));
+    }
+
+    #[test]
+    fn rewrites_merged_sandwicher_ids() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        migrate(&mut store).unwrap();
+        store.write_block(&DbBlock { slot: 10, ts: 0, tx_count: 6 }, &[sandwich(10, 5, vec![]), sandwich(10, 7, vec![])]).unwrap();
+        store.write_block(&DbBlock { slot: 11, ts: 0, tx_count: 3 }, &[sandwich(11, 3, vec![5])]).unwrap();
+        let mut stmt = store.conn.prepare("select slot, sandwicher_id from sandwich order by sandwicher_id, slot").unwrap();
+        let ids = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap().collect::<Result<Vec<(i64, i64)>, _>>().unwrap();
+        assert_eq!(ids, vec![(10, 3), (11, 3), (10, 7)]);
+    }
+}